@@ -17,6 +17,18 @@ impl PlainMatrix {
             dim: self.dim_m,
         }
     }
+    /// The transposed matrix.
+    pub fn transpose(&self) -> PlainMatrix {
+        let plainmatrix: Vec<Vec<f64>> = (0..self.dim_m)
+            .map(|j| (0..self.dim_n).map(|i| self.plainmatrix[i][j]).collect())
+            .collect();
+        PlainMatrix {
+            plainmatrix,
+            dim_n: self.dim_m,
+            dim_m: self.dim_n,
+        }
+    }
+
     pub fn new(v_matrix: Vec<Vec<f64>>) -> PlainMatrix {
         let n = v_matrix.len();
         let m = v_matrix[0].len();
@@ -115,7 +127,33 @@ impl Mul<&clear::vector::PlainVector> for &PlainMatrix {
         }
         clear::vector::PlainVector {
             plainvector: result,
-            dim: other.dim,
+            dim: self.dim_n,
+        }
+    }
+}
+
+// Multiplies two plainmatrix using the `*` operator.
+impl Mul<&PlainMatrix> for &PlainMatrix {
+    type Output = PlainMatrix;
+
+    fn mul(self, other: &PlainMatrix) -> Self::Output {
+        let mut result: Vec<Vec<f64>> = Vec::new();
+        for i in 0..self.dim_n {
+            let mut result_row: Vec<f64> = Vec::new();
+            for j in 0..other.dim_m {
+                let mut v: f64 = 0.;
+                for k in 0..self.dim_m {
+                    v += self.plainmatrix[i][k] * other.plainmatrix[k][j];
+                }
+                result_row.push(v);
+            }
+            result.push(result_row);
+        }
+
+        PlainMatrix {
+            plainmatrix: result,
+            dim_n: self.dim_n,
+            dim_m: other.dim_m,
         }
     }
 }
@@ -9,9 +9,17 @@ mod clear;
 mod reg;
 
 use clap::{ArgEnum, Parser};
-use reg::accuracy::calculate_accuracy;
-use reg::classifier::{classify_data_fhe, classify_data_plain};
+use reg::accuracy::{calculate_accuracy, calculate_accuracy_fhe, encrypt_labels_fhe};
+use reg::classifier::{classify_data_fhe, classify_data_fhe_streaming, classify_data_plain};
+use reg::estimator::{estimate_classification_fhe, estimate_training_fhe};
+use reg::interop::{export_model, import_model, ExportFormat};
 use reg::trainer::{train_data_fhe, train_data_plain};
+use reg::dataset::{DatasetFormat, DatasetOptions};
+use reg::error::Error;
+use reg::model::FeatureMismatch;
+use reg::replay::Replay;
+use reg::scaling::ScalingKind;
+use reg::utils::ParseMode;
 
 // We determine the cryptographic parameters depending on the compilation flag used.
 const PARAMS: zqz::Parameters =
@@ -36,7 +44,56 @@ fn main() -> Result<(), CryptoAPIError> {
     let prediction_file = args.prediction;
     let enable_encryption = args.enable_encryption;
     let operation = args.command;
+    let dry_run = args.dry_run;
+    let feature_mismatch = args.feature_mismatch;
+    let profile_file = args.profile;
+    let format = args.format;
+    let dataset_format = match format {
+        Some(Format::Libsvm) => Some(DatasetFormat::Libsvm),
+        Some(Format::Csv) => Some(DatasetFormat::Csv),
+        Some(Format::Json) if operation != Commands::Model => {
+            println!("json isn't a dataset format");
+            std::process::exit(1);
+        }
+        _ => None,
+    };
+    let options = DatasetOptions {
+        format: dataset_format,
+        mode: if args.lenient {
+            ParseMode::Lenient
+        } else {
+            ParseMode::Strict
+        },
+        has_header: args.header,
+        label_column: args.label_column,
+        delimiter: args.delimiter,
+        positive_label: args.positive_label,
+        negative_label: args.negative_label,
+        scaling: args.scaling,
+    };
     match &operation {
+        &Commands::Train if dry_run => {
+            if data_file.is_none() {
+                println!("Please fill the data_file option using -d");
+                std::process::exit(1);
+            }
+            exit_on_error(estimate_training_fhe(&data_file.unwrap(), &options));
+        }
+        &Commands::Classify if dry_run => {
+            if data_file.is_none() {
+                println!("Please fill the data_file option using -d");
+                std::process::exit(1);
+            }
+            if model_file.is_none() {
+                println!("Please fill the model_file option using -m");
+                std::process::exit(1);
+            }
+            exit_on_error(estimate_classification_fhe(
+                &data_file.unwrap(),
+                &model_file.unwrap(),
+                &options,
+            ));
+        }
         &Commands::Train if enable_encryption => {
             if data_file.is_none() {
                 println!("Please fill the data_file option using -d");
@@ -46,7 +103,12 @@ fn main() -> Result<(), CryptoAPIError> {
                 println!("Please fill the model_file option using -m");
                 std::process::exit(1);
             }
-            train_data_fhe(&data_file.unwrap(), &model_file.unwrap());
+            exit_on_error(train_data_fhe(
+                &data_file.unwrap(),
+                &model_file.unwrap(),
+                &options,
+                args.resume,
+            ));
             println!("Trained successfully!");
             println!("generated model file!");
         }
@@ -59,7 +121,7 @@ fn main() -> Result<(), CryptoAPIError> {
                 println!("Please fill the model_file option using -m");
                 std::process::exit(1);
             }
-            train_data_plain(&data_file.unwrap(), &model_file.unwrap());
+            exit_on_error(train_data_plain(&data_file.unwrap(), &model_file.unwrap(), &options));
             println!("Trained successfully!");
             println!("generated model file!");
         }
@@ -76,11 +138,43 @@ fn main() -> Result<(), CryptoAPIError> {
                 println!("Please fill the prediction_file option using -p");
                 std::process::exit(1);
             }
-            classify_data_fhe(
-                &data_file.unwrap(),
-                &model_file.unwrap(),
-                &prediction_file.unwrap(),
-            );
+            match args.batch_size {
+                Some(_) if args.encrypted_predictions.is_some() => {
+                    println!("The encrypted predictions can't be saved with --batch-size");
+                    std::process::exit(1);
+                }
+                Some(batch_size) => {
+                    exit_on_error(classify_data_fhe_streaming(
+                        &data_file.unwrap(),
+                        &model_file.unwrap(),
+                        &prediction_file.unwrap(),
+                        &options,
+                        feature_mismatch,
+                        batch_size.max(1),
+                        args.resume,
+                    ));
+                }
+                None => {
+                    let replay_mode = match (args.record, args.replay) {
+                        (Some(directory), None) => Some(Replay::Record(directory)),
+                        (None, Some(directory)) => Some(Replay::Replay(directory)),
+                        (None, None) => None,
+                        (Some(_), Some(_)) => {
+                            println!("Please choose between --record and --replay");
+                            std::process::exit(1);
+                        }
+                    };
+                    exit_on_error(classify_data_fhe(
+                        &data_file.unwrap(),
+                        &model_file.unwrap(),
+                        &prediction_file.unwrap(),
+                        &options,
+                        feature_mismatch,
+                        replay_mode.as_ref(),
+                        args.encrypted_predictions.as_deref(),
+                    ));
+                }
+            }
             println!("Classified successfully!");
             println!("generated prediction file!");
         }
@@ -97,14 +191,65 @@ fn main() -> Result<(), CryptoAPIError> {
                 println!("Please fill the prediction_file option using -p");
                 std::process::exit(1);
             }
-            classify_data_plain(
+            exit_on_error(classify_data_plain(
                 &data_file.unwrap(),
                 &model_file.unwrap(),
                 &prediction_file.unwrap(),
-            );
+                &options,
+                feature_mismatch,
+            ));
             println!("Classified successfully!");
             println!("generated prediction file!");
         }
+        &Commands::Labels => {
+            if data_file.is_none() {
+                println!("Please fill the data_file option using -d");
+                std::process::exit(1);
+            }
+            if model_file.is_none() {
+                println!("Please fill the model_file option using -m");
+                std::process::exit(1);
+            }
+            if args.encrypted_labels.is_none() {
+                println!("Please fill the encrypted_labels option using --encrypted-labels");
+                std::process::exit(1);
+            }
+            let nb_labels = exit_on_error(encrypt_labels_fhe(
+                &data_file.unwrap(),
+                &model_file.unwrap(),
+                &args.encrypted_labels.unwrap(),
+                &options,
+            ));
+            println!("Encrypted {} labels!", nb_labels);
+        }
+        &Commands::Accuracy if enable_encryption => {
+            if args.encrypted_predictions.is_none() {
+                println!(
+                    "Please fill the encrypted_predictions option using --encrypted-predictions"
+                );
+                std::process::exit(1);
+            }
+            if args.encrypted_labels.is_none() {
+                println!("Please fill the encrypted_labels option using --encrypted-labels");
+                std::process::exit(1);
+            }
+            let matrix = exit_on_error(calculate_accuracy_fhe(
+                &args.encrypted_predictions.unwrap(),
+                &args.encrypted_labels.unwrap(),
+            ));
+            println!(
+                "Accuracy: {}%, over {} records",
+                matrix.accuracy() * 100.,
+                matrix.total()
+            );
+            println!(
+                "True positives: {}, false positives: {}, true negatives: {}, false negatives: {}",
+                matrix.true_positives,
+                matrix.false_positives,
+                matrix.true_negatives,
+                matrix.false_negatives
+            );
+        }
         &Commands::Accuracy => {
             if data_file.is_none() {
                 println!("Please fill the data_file option using -d");
@@ -114,23 +259,79 @@ fn main() -> Result<(), CryptoAPIError> {
                 println!("Please fill the prediction_file option using -p");
                 std::process::exit(1);
             }
-            let (accuracy, total_records) =
-                calculate_accuracy(&prediction_file.unwrap(), &data_file.unwrap());
-            if accuracy < 0. {
-                println!("Error happened!");
-                std::process::exit(1);
-            }
+            let (accuracy, total_records) = exit_on_error(calculate_accuracy(
+                &prediction_file.unwrap(),
+                &data_file.unwrap(),
+                &options,
+            ));
             println!(
                 "Accuracy: {}%, over {} records",
                 accuracy * 100.,
                 total_records
             );
         }
+        &Commands::Model => {
+            if model_file.is_none() {
+                println!("Please fill the model_file option using -m");
+                std::process::exit(1);
+            }
+            match args.action {
+                Some(ModelAction::Export) => {
+                    let export_format = match format {
+                        None | Some(Format::Json) => ExportFormat::Json,
+                        Some(_) => {
+                            println!("models can only be exported as json");
+                            std::process::exit(1);
+                        }
+                    };
+                    exit_on_error(export_model(
+                        &model_file.unwrap(),
+                        args.output.as_deref(),
+                        export_format,
+                    ));
+                }
+                Some(ModelAction::Import) => {
+                    if args.input.is_none() {
+                        println!("Please fill the input option using -i");
+                        std::process::exit(1);
+                    }
+                    exit_on_error(import_model(
+                        &args.input.unwrap(),
+                        &model_file.unwrap(),
+                        &options,
+                    ));
+                    println!("Imported successfully!");
+                    println!("generated model file!");
+                }
+                None => {
+                    println!("Please choose the model action: export or import");
+                    std::process::exit(1);
+                }
+            }
+        }
         &_ => {}
     }
+    if let Some(profile_file) = profile_file {
+        if let Err(e) = zqz::profile::save_report(&profile_file) {
+            println!("{}: {}", profile_file, e);
+            std::process::exit(1);
+        }
+        println!("generated profile file!");
+    }
     Ok(())
 }
 
+// Prints the error and stops the program if the data or model file couldn't be used.
+fn exit_on_error<T>(result: Result<T, Error>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            println!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
 /// Simple program to greet a person
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -138,6 +339,9 @@ fn main() -> Result<(), CryptoAPIError> {
 struct Args {
     #[clap(arg_enum)]
     command: Commands,
+    /// action of the model command
+    #[clap(arg_enum)]
+    action: Option<ModelAction>,
     /// training data file
     #[clap(short, long)]
     data: Option<String>,
@@ -151,6 +355,85 @@ struct Args {
     /// enable, disable fhe, disabled by default
     #[clap(short, long)]
     enable_encryption: bool,
+
+    /// estimate the cost of the encrypted train or classify without running it
+    #[clap(long)]
+    dry_run: bool,
+
+    /// profiling report file, written as JSON
+    #[clap(long)]
+    profile: Option<String>,
+
+    /// skip and count the malformed lines of the data file instead of stopping at the first one
+    #[clap(long)]
+    lenient: bool,
+
+    /// format of the data file, guessed from its extension by default, or of the exported model
+    #[clap(long, arg_enum)]
+    format: Option<Format>,
+
+    /// exported model file, the model is printed if missing
+    #[clap(short, long)]
+    output: Option<String>,
+
+    /// coefficients file to import, as JSON
+    #[clap(short, long)]
+    input: Option<String>,
+
+    /// the first line of the csv data file holds the names of the columns
+    #[clap(long)]
+    header: bool,
+
+    /// index, or name if there is a header, of the label column of the csv data file
+    #[clap(long, default_value = "0")]
+    label_column: String,
+
+    /// delimiter of the columns of the csv data file
+    #[clap(long, default_value = ",")]
+    delimiter: char,
+
+    /// label of the positive class, detected from the training data by default
+    #[clap(long)]
+    positive_label: Option<String>,
+
+    /// label of the negative class, detected from the training data by default
+    #[clap(long)]
+    negative_label: Option<String>,
+
+    /// scaling of the characteristics computed by the training and stored in the model
+    #[clap(long, arg_enum, default_value = "none")]
+    scaling: ScalingKind,
+
+    /// what to do with the characteristics of the data file unseen by the model
+    #[clap(long, arg_enum, default_value = "warn")]
+    feature_mismatch: FeatureMismatch,
+
+    /// classify the encrypted data this many records at a time, appending to the prediction file
+    #[clap(long)]
+    batch_size: Option<usize>,
+
+    /// continue an interrupted job from where it stopped
+    #[clap(long)]
+    resume: bool,
+
+    /// encrypted predictions file, written by the encrypted classification and read by the
+    /// encrypted accuracy
+    #[clap(long)]
+    encrypted_predictions: Option<String>,
+
+    /// encrypted labels file, written by the labels command and read by the encrypted accuracy
+    #[clap(long)]
+    encrypted_labels: Option<String>,
+
+    /// INSECURE, for debugging only: save the keys, secret key included, and the ciphertexts of
+    /// the encrypted classification to this directory
+    #[clap(long)]
+    record: Option<String>,
+
+    /// INSECURE, for debugging only: classify with the keys and the ciphertexts recorded in this
+    /// directory, reproducing the recorded run exactly
+    #[clap(long)]
+    replay: Option<String>,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum, Debug)]
@@ -159,4 +442,22 @@ enum Commands {
     Train,
     Classify,
     Accuracy,
+    Model,
+    /// Encrypts the labels of the data file for the encrypted accuracy
+    Labels,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum, Debug)]
+enum ModelAction {
+    /// Writes the model in another format
+    Export,
+    /// Builds a model from a JSON coefficients file
+    Import,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum, Debug)]
+enum Format {
+    Libsvm,
+    Csv,
+    Json,
 }
@@ -1,23 +1,343 @@
 use crate::reg;
+use crate::zqz;
 
-use reg::utils::{load_vector, parse_data_file};
+use reg::dataset::{load_dataset, DatasetOptions};
+use reg::error::Error;
+use reg::labels::same_label;
+use reg::model::Model;
+use reg::utils::load_labels;
 
-pub fn calculate_accuracy(prediction_file: &str, data_file: &str) -> (f64, f64) {
-    let predictions: Vec<f64> = load_vector(prediction_file);
-    let (_, y) = parse_data_file(&data_file);
+use crate::measure_duration;
+use crate::PARAMS;
+
+use zqz::cipherint::{self, CipherInt};
+use zqz::keys::EncryptKey;
+
+pub fn calculate_accuracy(
+    prediction_file: &str,
+    data_file: &str,
+    options: &DatasetOptions,
+) -> Result<(f64, f64), Error> {
+    let predictions: Vec<String> = load_labels(prediction_file)?;
+    let (_, y) = load_dataset(&data_file, options)?;
+    if y.is_empty() {
+        return Err(Error::EmptyDataset(data_file.to_string()));
+    }
     if y.len() != predictions.len() {
-        //TODO throw Error
-        println!("The size of the files don't match!");
-        return (-1.,-1.);
+        return Err(Error::Io(
+            prediction_file.to_string(),
+            format!("{} predictions for {} records", predictions.len(), y.len()),
+        ));
     }
     let mut correct_answers = 0.;
     let mut total_answers = 0.;
 
     for i in 0..y.len() {
-        if y[i] == predictions[i] {
+        if same_label(&y[i], &predictions[i]) {
             correct_answers += 1.;
         }
         total_answers += 1.;
     }
-    (correct_answers / total_answers, total_answers )
+    Ok((correct_answers / total_answers, total_answers))
+}
+
+/// The outcomes of the predictions of a binary classifier.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ConfusionMatrix {
+    pub true_positives: u64,
+    pub false_positives: u64,
+    pub true_negatives: u64,
+    pub false_negatives: u64,
+}
+
+impl ConfusionMatrix {
+    pub fn total(&self) -> u64 {
+        self.true_positives + self.false_positives + self.true_negatives + self.false_negatives
+    }
+
+    /// The share of the correct predictions, 0 when there is none.
+    pub fn accuracy(&self) -> f64 {
+        if self.total() == 0 {
+            return 0.;
+        }
+        (self.true_positives + self.true_negatives) as f64 / self.total() as f64
+    }
+
+    /// Decrypts the counts of the outcomes.
+    pub fn decrypt(sk: &EncryptKey, outcomes: &EncryptedOutcomes) -> ConfusionMatrix {
+        ConfusionMatrix {
+            true_positives: outcomes.true_positives.decrypt(sk),
+            false_positives: outcomes.false_positives.decrypt(sk),
+            true_negatives: outcomes.true_negatives.decrypt(sk),
+            false_negatives: outcomes.false_negatives.decrypt(sk),
+        }
+    }
+}
+
+// The base of the digits of the encrypted counts.
+fn digit_base() -> u64 {
+    PARAMS.modulo as u64 / 2
+}
+
+// The number of records of a block, whose counts are computed modulo PARAMS.modulo before being
+// added to the totals. A digit, at most digit_base() - 1, plus the count of a block stays at most
+// PARAMS.modulo - 1, so their sum is exact and its carry can be looked up.
+fn block_size() -> usize {
+    PARAMS.modulo - digit_base() as usize
+}
+
+/// A count encrypted as digits in base `digit_base()`, the lowest first, so that it can exceed
+/// PARAMS.modulo.
+pub struct EncryptedCount {
+    digits: Vec<CipherInt>,
+}
+
+impl EncryptedCount {
+    // A count of 0 with enough digits to hold `max`, `zero` being an encryption of 0.
+    fn zero(zero: &CipherInt, max: usize) -> EncryptedCount {
+        let mut nb_digits = 1;
+        let mut capacity = digit_base();
+        while capacity <= max as u64 {
+            nb_digits += 1;
+            capacity *= digit_base();
+        }
+        EncryptedCount {
+            digits: vec![zero.clone(); nb_digits],
+        }
+    }
+
+    // Adds the count of a block, propagating the carries through the digits.
+    fn add(&mut self, count: &CipherInt) {
+        let base = digit_base();
+        let mut carry = count.clone();
+        for digit in self.digits.iter_mut() {
+            let sum = &*digit + &carry;
+            *digit = sum.lookup(move |k| k % base);
+            carry = sum.lookup(move |k| k / base);
+        }
+    }
+
+    pub fn decrypt(&self, sk: &EncryptKey) -> u64 {
+        self.digits
+            .iter()
+            .rev()
+            .fold(0, |count, digit| count * digit_base() + sk.decrypt_int(digit))
+    }
+}
+
+/// The encrypted outcomes of the predictions.
+pub struct EncryptedOutcomes {
+    pub true_positives: EncryptedCount,
+    pub false_positives: EncryptedCount,
+    pub true_negatives: EncryptedCount,
+    pub false_negatives: EncryptedCount,
+}
+
+/// Counts the outcomes of the encrypted predictions against the encrypted labels, both being 1 for
+/// the positive class and 0 for the negative one. The records are counted by blocks modulo
+/// PARAMS.modulo, and the counts of the blocks are summed under encryption so that only the totals
+/// can be decrypted. There has to be at least one prediction.
+pub fn count_outcomes_fhe(e_predictions: &[CipherInt], e_labels: &[CipherInt]) -> EncryptedOutcomes {
+    let zero = e_predictions[0].lookup(|_| 0);
+    let total = |n: usize| EncryptedCount::zero(&zero, n);
+    let n = e_predictions.len();
+    let mut outcomes = EncryptedOutcomes {
+        true_positives: total(n),
+        false_positives: total(n),
+        true_negatives: total(n),
+        false_negatives: total(n),
+    };
+    for (e_p, e_y) in e_predictions
+        .chunks(block_size())
+        .zip(e_labels.chunks(block_size()))
+    {
+        // p + y is 2 for the true positives only
+        let true_positive = |i: usize| (&e_p[i] + &e_y[i]).lookup(|k| (k == 2) as u64);
+        let mut predicted_positives = e_p[0].clone();
+        let mut positives = e_y[0].clone();
+        let mut true_positives = true_positive(0);
+        for i in 1..e_p.len() {
+            predicted_positives = &predicted_positives + &e_p[i];
+            positives = &positives + &e_y[i];
+            true_positives = &true_positives + &true_positive(i);
+        }
+        // tn = n - (tp + fp) - (tp + fn) + tp
+        let true_negatives =
+            &(&(&true_positives - &predicted_positives) - &positives) + e_p.len() as u64;
+        outcomes
+            .false_positives
+            .add(&(&predicted_positives - &true_positives));
+        outcomes.false_negatives.add(&(&positives - &true_positives));
+        outcomes.true_positives.add(&true_positives);
+        outcomes.true_negatives.add(&true_negatives);
+    }
+    outcomes
+}
+
+/// Saves the encrypted classes, 1 for the positive class and 0 for the negative one.
+pub fn save_encrypted_classes(e_classes: &[CipherInt], file: &str) -> Result<(), Error> {
+    std::fs::write(file, cipherint::to_bytes(e_classes))
+        .map_err(|e| Error::Io(file.to_string(), e.to_string()))
+}
+
+/// Loads the classes saved by `save_encrypted_classes`, encrypted with the keys of `sk`.
+pub fn load_encrypted_classes(file: &str, sk: &EncryptKey) -> Result<Vec<CipherInt>, Error> {
+    let bytes = std::fs::read(file).map_err(|e| Error::Io(file.to_string(), e.to_string()))?;
+    cipherint::from_bytes(&bytes, sk)
+        .ok_or_else(|| Error::Io(file.to_string(), "not encrypted classes".to_string()))
+}
+
+// Loads the existing keys, the classes have to be encrypted with them.
+fn load_keys() -> Result<EncryptKey, Error> {
+    if !EncryptKey::keys_exist(&PARAMS.gen_prefix()) {
+        return Err(Error::Io(
+            EncryptKey::secret_key_file(&PARAMS.gen_prefix()),
+            "no keys, the classes have to be encrypted first".to_string(),
+        ));
+    }
+    Ok(EncryptKey::load_from_files(&PARAMS.gen_prefix()))
+}
+
+/// Encrypts the labels of the data file as classes of the model to `labels_file`, for the data
+/// owner to hand them to `calculate_accuracy_fhe` without revealing them.
+pub fn encrypt_labels_fhe(
+    data_file: &str,
+    model_file: &str,
+    labels_file: &str,
+    options: &DatasetOptions,
+) -> Result<usize, Error> {
+    let (_, y) = load_dataset(&data_file, options)?;
+    let model = Model::load(model_file)?;
+    let classes = model.labels.encode(&y)?;
+    measure_duration!("1. Key Loading...",[
+        let sk = load_keys()?;
+    ]);
+    measure_duration!("2. Encryption... ",[
+        let e_y : Vec<CipherInt>        = classes
+            .iter()
+            .map(|class| sk.encrypt_int((*class > 0.) as u64))
+            .collect();
+    ]);
+    save_encrypted_classes(&e_y, labels_file)?;
+    Ok(e_y.len())
+}
+
+/// Computes the confusion matrix of the encrypted predictions against the encrypted labels, saved
+/// by the encrypted classification and by `encrypt_labels_fhe` with the existing keys. They are
+/// compared homomorphically, and only the totals of the outcomes are decrypted.
+pub fn calculate_accuracy_fhe(
+    predictions_file: &str,
+    labels_file: &str,
+) -> Result<ConfusionMatrix, Error> {
+    measure_duration!("1. Key Loading...",[
+        let sk = load_keys()?;
+    ]);
+    let e_p = load_encrypted_classes(predictions_file, &sk)?;
+    let e_y = load_encrypted_classes(labels_file, &sk)?;
+    if e_y.is_empty() {
+        return Err(Error::EmptyDataset(labels_file.to_string()));
+    }
+    if e_y.len() != e_p.len() {
+        return Err(Error::Io(
+            predictions_file.to_string(),
+            format!("{} predictions for {} labels", e_p.len(), e_y.len()),
+        ));
+    }
+    measure_duration!("2. Counting... ",[
+        let outcomes = count_outcomes_fhe(&e_p, &e_y);
+    ]);
+    measure_duration!("3. Decryption... ",[
+        let matrix = ConfusionMatrix::decrypt(&sk, &outcomes);
+    ]);
+    Ok(matrix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zqz::testing::*;
+
+    #[test]
+    fn count_outcomes_by_blocks() {
+        let sk = zero_key();
+        // Mostly true negatives, more than PARAMS.modulo, the last block being partial
+        let n = 80;
+        let p: Vec<u64> = (0..n).map(|i| (i % 16 == 0 || i % 16 == 2) as u64).collect();
+        let y: Vec<u64> = (0..n).map(|i| (i % 16 < 2) as u64).collect();
+        let e_p: Vec<CipherInt> = p.iter().map(|bit| sk.encrypt_int(*bit)).collect();
+        let e_y: Vec<CipherInt> = y.iter().map(|bit| sk.encrypt_int(*bit)).collect();
+
+        let outcomes = count_outcomes_fhe(&e_p, &e_y);
+        assert_eq!(outcomes.true_positives.digits.len(), 2);
+        let count = |predicted: u64, label: u64| {
+            (0..n).filter(|i| p[*i] == predicted && y[*i] == label).count() as u64
+        };
+        let expected = ConfusionMatrix {
+            true_positives: count(1, 1),
+            false_positives: count(1, 0),
+            true_negatives: count(0, 0),
+            false_negatives: count(0, 1),
+        };
+        assert!(expected.true_negatives >= PARAMS.modulo as u64);
+        let matrix = ConfusionMatrix::decrypt(&sk, &outcomes);
+        assert_eq!(matrix, expected);
+        assert_eq!(matrix.total(), n as u64);
+    }
+
+    #[test]
+    fn full_blocks_are_counted_exactly() {
+        let sk = zero_key();
+        // The first block leaves a digit of block_size() - 1, the full block of true positives
+        // which follows brings the sum to PARAMS.modulo - 1
+        let n = 2 * block_size();
+        let bits: Vec<u64> = (0..n).map(|i| (i > 0) as u64).collect();
+        let e_bits: Vec<CipherInt> = bits.iter().map(|bit| sk.encrypt_int(*bit)).collect();
+
+        let outcomes = count_outcomes_fhe(&e_bits, &e_bits);
+        let matrix = ConfusionMatrix::decrypt(&sk, &outcomes);
+        let expected = ConfusionMatrix {
+            true_positives: n as u64 - 1,
+            true_negatives: 1,
+            ..ConfusionMatrix::default()
+        };
+        assert_eq!(matrix, expected);
+    }
+
+    #[test]
+    fn accuracy_of_mismatched_files() {
+        let prediction_file = temp_file("accuracy_mismatched_predictions");
+        std::fs::write(&prediction_file, "1\n").unwrap();
+        let data_file = temp_file("accuracy_mismatched_data");
+        std::fs::write(&data_file, "1 1:0.5\n-1 1:0.25\n").unwrap();
+        let result = calculate_accuracy(&prediction_file, &data_file, &DatasetOptions::default());
+        assert!(matches!(result, Err(Error::Io(file, _)) if file == prediction_file));
+    }
+
+    #[test]
+    fn counts_carry_through_their_digits() {
+        let sk = zero_key();
+        let base = digit_base();
+        let mut count = EncryptedCount::zero(&sk.encrypt_int(0), (base * base) as usize);
+        assert_eq!(count.digits.len(), 3);
+        for _ in 0..base + 1 {
+            count.add(&sk.encrypt_int(base));
+        }
+        assert_eq!(count.decrypt(&sk), base * (base + 1));
+    }
+
+    #[test]
+    fn accuracy_of_no_prediction() {
+        assert_eq!(ConfusionMatrix::default().accuracy(), 0.);
+    }
+
+    #[test]
+    fn encrypted_classes_round_trip() {
+        let sk = zero_key();
+        let file = temp_file("accuracy_encrypted_classes");
+        let e_classes = vec![sk.encrypt_int(1), sk.encrypt_int(0)];
+        save_encrypted_classes(&e_classes, &file).unwrap();
+        assert_eq!(load_encrypted_classes(&file, &sk).unwrap(), e_classes);
+        std::fs::write(&file, "1\n-1\n").unwrap();
+        assert!(matches!(load_encrypted_classes(&file, &sk), Err(Error::Io(_, _))));
+    }
 }
@@ -0,0 +1,241 @@
+//! A module saving the state of an encrypted training, so that an interrupted training can be
+//! resumed.
+use crate::reg;
+use crate::zqz;
+
+use reg::labels::LabelMapping;
+use reg::planner::EncodingPlan;
+use reg::scaling::Scaler;
+use reg::utils::{hash_bytes, hash_file};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use zqz::keys::EncryptKey;
+use zqz::vector::CipherVector;
+
+// The first bytes of a checkpoint file.
+const MAGIC: &[u8; 8] = b"FHELRCKP";
+
+/// The problems that can be found while saving or resuming from a checkpoint.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CheckpointError {
+    // The file could not be read or written
+    Io(String, String),
+    // The file doesn't hold a checkpoint
+    Corrupted(String),
+    // The checkpoint was encrypted with other keys than the current ones
+    KeysMismatch(String, String),
+    // The checkpoint was computed on another dataset
+    DatasetMismatch(String),
+    // The checkpoint was computed with other labels, scaling or encoding
+    SettingsMismatch(String),
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheckpointError::Io(file, e) => {
+                write!(f, "{}: could not access the checkpoint: {}", file, e)
+            }
+            CheckpointError::Corrupted(file) => write!(f, "{}: not a checkpoint file", file),
+            CheckpointError::KeysMismatch(file, prefix) => write!(
+                f,
+                "{}: the checkpoint was encrypted with other keys than the ones of {}",
+                file, prefix
+            ),
+            CheckpointError::DatasetMismatch(file) => write!(
+                f,
+                "{}: the checkpoint was computed on another data file",
+                file
+            ),
+            CheckpointError::SettingsMismatch(file) => write!(
+                f,
+                "{}: the checkpoint was computed with other labels, scaling or encoding",
+                file
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+/// Hashes what the training derives from the options: the mapping of the labels, the scaling of
+/// the characteristics and the encoding of the ciphertexts.
+pub fn settings_hash(labels: &LabelMapping, scaling: &Scaler, plan: &EncodingPlan) -> String {
+    hash_bytes(format!("{:?} {:?} {:?}", labels, scaling, plan).as_bytes())
+}
+
+/// The state of an encrypted training after an iteration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    // The iteration to resume from
+    pub next_iteration: usize,
+    pub keys_prefix: String,
+    // The FNV-1a hashes of the secret key file and of the training data file
+    pub keys_hash: String,
+    pub dataset_hash: String,
+    // The hash of the settings of the training, see `settings_hash`
+    pub settings_hash: String,
+    // The serialized ciphertexts of beta
+    e_beta: Vec<u8>,
+}
+
+impl Checkpoint {
+    pub fn new(
+        next_iteration: usize,
+        keys_prefix: &str,
+        dataset_hash: &str,
+        settings_hash: &str,
+        e_beta: &CipherVector,
+    ) -> Checkpoint {
+        Checkpoint {
+            next_iteration,
+            keys_prefix: keys_prefix.to_string(),
+            keys_hash: hash_file(&EncryptKey::secret_key_file(keys_prefix)).unwrap_or_default(),
+            dataset_hash: dataset_hash.to_string(),
+            settings_hash: settings_hash.to_string(),
+            e_beta: e_beta.to_bytes(),
+        }
+    }
+
+    /// Saves the checkpoint, the previous one is only replaced once the new one is fully written
+    pub fn save(&self, filename: &str) -> Result<(), CheckpointError> {
+        let io_error = |e: std::io::Error| CheckpointError::Io(filename.to_string(), e.to_string());
+        let mut encoded: Vec<u8> = MAGIC.to_vec();
+        encoded.extend(bincode::serialize(&self).unwrap());
+        let tmp_file = format!("{}.tmp", filename);
+        std::fs::write(&tmp_file, &encoded).map_err(io_error)?;
+        std::fs::rename(&tmp_file, filename).map_err(io_error)
+    }
+
+    /// Loads the checkpoint, if there is one
+    pub fn load(filename: &str) -> Result<Option<Checkpoint>, CheckpointError> {
+        let data = match std::fs::read(filename) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(CheckpointError::Io(filename.to_string(), e.to_string())),
+        };
+        if !data.starts_with(MAGIC) {
+            return Err(CheckpointError::Corrupted(filename.to_string()));
+        }
+        bincode::deserialize(&data[MAGIC.len()..])
+            .map(Some)
+            .map_err(|_| CheckpointError::Corrupted(filename.to_string()))
+    }
+
+    /// Restores the encrypted beta, after checking that the checkpoint was computed with the keys
+    /// of `keys_prefix` on the data file of hash `dataset_hash`, with the settings of hash
+    /// `settings_hash`.
+    pub fn restore(
+        &self,
+        filename: &str,
+        sk: &EncryptKey,
+        keys_prefix: &str,
+        dataset_hash: &str,
+        settings_hash: &str,
+    ) -> Result<CipherVector, CheckpointError> {
+        let keys_hash = hash_file(&EncryptKey::secret_key_file(keys_prefix)).unwrap_or_default();
+        if self.keys_prefix != keys_prefix || self.keys_hash != keys_hash {
+            return Err(CheckpointError::KeysMismatch(
+                filename.to_string(),
+                keys_prefix.to_string(),
+            ));
+        }
+        if self.dataset_hash != dataset_hash {
+            return Err(CheckpointError::DatasetMismatch(filename.to_string()));
+        }
+        if self.settings_hash != settings_hash {
+            return Err(CheckpointError::SettingsMismatch(filename.to_string()));
+        }
+        CipherVector::from_bytes(&self.e_beta, sk)
+            .ok_or_else(|| CheckpointError::Corrupted(filename.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zqz::testing::{encoder, temp_file, zero_key};
+    use reg::scaling::ScalingKind;
+
+    const KEYS_PREFIX: &str = "checkpoint_test_keys";
+
+    fn plan() -> EncodingPlan {
+        EncodingPlan::for_classification(&vec![vec![1., 2.]], &vec![0.5, 0.5])
+    }
+
+    #[test]
+    fn save_load_and_restore() {
+        let sk = zero_key();
+        let e_beta = sk.encrypt_vector_with_encoder(&vec![0.5, -0.25], &encoder());
+        let settings = settings_hash(&LabelMapping::default(), &Scaler::default(), &plan());
+        let file = temp_file("checkpoint_restore");
+        let file = file.as_str();
+        Checkpoint::new(3, KEYS_PREFIX, "data", &settings, &e_beta)
+            .save(file)
+            .unwrap();
+
+        let checkpoint = Checkpoint::load(file).unwrap().unwrap();
+        assert_eq!(checkpoint.next_iteration, 3);
+        let restored = checkpoint.restore(file, &sk, KEYS_PREFIX, "data", &settings);
+        assert_eq!(restored, Ok(e_beta));
+        assert_eq!(
+            checkpoint.restore(file, &sk, KEYS_PREFIX, "other data", &settings),
+            Err(CheckpointError::DatasetMismatch(file.to_string()))
+        );
+        assert_eq!(
+            checkpoint.restore(file, &sk, "other_keys", "data", &settings),
+            Err(CheckpointError::KeysMismatch(file.to_string(), "other_keys".to_string()))
+        );
+    }
+
+    #[test]
+    fn restore_with_other_keys_of_the_same_prefix() {
+        let sk = zero_key();
+        let e_beta = sk.encrypt_vector_with_encoder(&vec![0.5], &encoder());
+        let settings = settings_hash(&LabelMapping::default(), &Scaler::default(), &plan());
+        // Only the secret key file is hashed, its content stands for the keys
+        let keys_prefix = temp_file("checkpoint_keys");
+        let secret_key_file = EncryptKey::secret_key_file(&keys_prefix);
+        std::fs::write(&secret_key_file, "first keys").unwrap();
+        let checkpoint = Checkpoint::new(2, &keys_prefix, "data", &settings, &e_beta);
+        assert_eq!(checkpoint.keys_hash, hash_bytes(b"first keys"));
+        assert_eq!(
+            checkpoint.restore("checkpoint", &sk, &keys_prefix, "data", &settings),
+            Ok(e_beta)
+        );
+
+        std::fs::write(&secret_key_file, "new keys").unwrap();
+        assert_eq!(
+            checkpoint.restore("checkpoint", &sk, &keys_prefix, "data", &settings),
+            Err(CheckpointError::KeysMismatch("checkpoint".to_string(), keys_prefix.clone()))
+        );
+    }
+
+    #[test]
+    fn restore_with_the_same_settings() {
+        let sk = zero_key();
+        let e_beta = sk.encrypt_vector_with_encoder(&vec![0.5], &encoder());
+        let labels = LabelMapping::default();
+        let settings = settings_hash(&labels, &Scaler::default(), &plan());
+        let checkpoint = Checkpoint::new(2, KEYS_PREFIX, "data", &settings, &e_beta);
+
+        let swapped = LabelMapping {
+            positive: labels.negative.clone(),
+            negative: labels.positive.clone(),
+            ..labels.clone()
+        };
+        let scaled = Scaler::fit(&vec![vec![1.], vec![3.]], ScalingKind::ZScore);
+        let mut wider = plan();
+        wider.max *= 2.;
+        for other in [
+            settings_hash(&swapped, &Scaler::default(), &plan()),
+            settings_hash(&labels, &scaled, &plan()),
+            settings_hash(&labels, &Scaler::default(), &wider),
+        ] {
+            assert_eq!(
+                checkpoint.restore("checkpoint", &sk, KEYS_PREFIX, "data", &other),
+                Err(CheckpointError::SettingsMismatch("checkpoint".to_string()))
+            );
+        }
+    }
+}
@@ -5,49 +5,141 @@ use crate::zqz;
 use clear::matrix::PlainMatrix;
 use clear::vector::PlainVector;
 
-use reg::utils::{load_encoded_vector, parse_data_file, save_vector};
+use reg::accuracy::save_encrypted_classes;
+use reg::dataset::{load_dataset, reader_for, DatasetOptions};
+use reg::planner::EncodingPlan;
+use reg::error::Error;
+use reg::model::{FeatureMismatch, Model, ModelError};
+use reg::replay::{record, replay, Replay};
+use reg::utils::{count_complete_lines, save_labels, truncate_partial_line, ParseError};
 
 use crate::measure_duration;
 use crate::PARAMS;
 
+use zqz::cipherfloat::Cipherfloat;
+use zqz::cipherint::CipherInt;
+use zqz::cost::OperationCount;
 use zqz::keys::EncryptKey;
 use zqz::matrix::CipherMatrix;
 use zqz::vector::CipherVector;
 
-pub fn classify_data_plain(data_file: &str, model_file: &str, prediction_file: &str) -> Vec<f64> {
-    let (mut x, _) = parse_data_file(&data_file);
-    let beta = load_encoded_vector(model_file);
+use std::fs::OpenOptions;
+use std::io::Write;
+
+pub fn classify_data_plain(
+    data_file: &str,
+    model_file: &str,
+    prediction_file: &str,
+    options: &DatasetOptions,
+    mismatch: FeatureMismatch,
+) -> Result<Vec<f64>, Error> {
+    let (mut x, _) = load_dataset(&data_file, options)?;
+    let model = Model::load(model_file)?;
     let mut predictions: Vec<f64> = Vec::new();
-    //Extending the columns of the matrix to be the size of beta
-    for i in 0..x.len() {
-        if x[i].len() < beta.len() {
-            for _ in x[i].len()..beta.len() {
-                x[i].push(0.);
-            }
-        }
-    }
+    //Aligning the columns of the matrix to the characteristics of the model
+    model.align(&mut x, mismatch)?;
+    model.scaling.transform(&mut x);
     let p_x = PlainMatrix::new(x);
-    let p_beta = PlainVector::new(beta);
+    let p_beta = PlainVector::new(model.beta);
+    let intercept = model.intercept.unwrap_or(0.);
 
     for i in 0 as usize..p_x.dim_n {
-        let (class, _) = sigmoid_classification(&p_beta, &p_x.get_row(i));
+        let (class, _) = sigmoid_classification(&p_beta, &p_x.get_row(i), intercept);
         predictions.push(class as f64);
     }
-    save_vector(&predictions, prediction_file);
-    predictions
+    save_labels(&model.labels.decode(&predictions), prediction_file)?;
+    Ok(predictions)
 }
 
-pub fn classify_data_fhe(data_file: &str, model_file: &str, prediction_file: &str) -> Vec<f64> {
-    let (x, _) = parse_data_file(&data_file);
+/// Classifies the encrypted data file, with `replay_mode` the keys and the ciphertexts of the run
+/// are recorded, or replaced by the recorded ones. With `encrypted_prediction_file`, the classes
+/// are also saved encrypted there, for `calculate_accuracy_fhe`.
+pub fn classify_data_fhe(
+    data_file: &str,
+    model_file: &str,
+    prediction_file: &str,
+    options: &DatasetOptions,
+    mismatch: FeatureMismatch,
+    replay_mode: Option<&Replay>,
+    encrypted_prediction_file: Option<&str>,
+) -> Result<Vec<f64>, Error> {
+    let (mut x, _) = load_dataset(&data_file, options)?;
     let mut predictions: Vec<f64> = Vec::new();
-    let beta = load_encoded_vector(model_file);
-    let mut encoding_limit = 0.;
-    
-    for b in &beta {
-        encoding_limit += b;
+    let model = Model::load(model_file)?;
+    //Aligning the columns of the matrix to the characteristics of the model
+    model.align(&mut x, mismatch)?;
+    //Folding the scaling into the model so that the data is encrypted as it is
+    let (beta, mut constant) = model.scaling.fold(&model.beta);
+    constant += model.intercept.unwrap_or(0.);
+    let plan = EncodingPlan::for_classification(&x, &beta);
+    plan.report();
+    let encoder = plan.encoder();
+
+    let (sk, e_x, e_beta) = match replay_mode {
+        Some(Replay::Replay(directory)) => {
+            measure_duration!("1. Replay Loading...",[
+                let replayed = replay(directory, data_file, model_file)?;
+            ]);
+            replayed
+        }
+        _ => {
+            measure_duration!("1. Key Loading...",[
+                let sk = if !EncryptKey::keys_exist(&PARAMS.gen_prefix()) {
+                    let key = EncryptKey::new();
+                    key.save_to_files(&PARAMS.gen_prefix());
+                    key
+                } else {
+                    EncryptKey::load_from_files(&PARAMS.gen_prefix())
+                };
+            ]);
+            measure_duration!("2. Encryption... ",[
+                let e_x : CipherMatrix          = sk.encrypt_matrix_with_encoder(&x, &encoder);
+                let e_beta : CipherVector       = sk.encrypt_vector_with_encoder(&beta, &encoder);
+            ]);
+            if let Some(Replay::Record(directory)) = replay_mode {
+                record(directory, data_file, model_file, &sk, &e_x, &e_beta)?;
+            }
+            (sk, e_x, e_beta)
+        }
+    };
+    let mut e_classes: Vec<Cipherfloat> = Vec::new();
+    measure_duration!(
+        "3. Classification... ",
+        [for i in 0..e_x.dim_n {
+            let xbeta = &e_x.get_row(i) * &e_beta;
+            let e_class = xbeta.bs_ks(|x| sigmoid(x + constant));
+            e_classes.push(e_class);
+        }]
+    );
+    if let Some(encrypted_prediction_file) = encrypted_prediction_file {
+        let e_bits: Vec<CipherInt> = e_classes.iter().map(|e_class| e_class.to_bit()).collect();
+        save_encrypted_classes(&e_bits, encrypted_prediction_file)?;
     }
-    encoding_limit = encoding_limit.floor().abs();
+    measure_duration!(
+        "4. Decryption... ",
+        [for e_class in &e_classes {
+            let mut p_class = sk.decrypt_float(e_class);
+            p_class = if p_class > 0. { 1. } else { -1. };
+            predictions.push(p_class);
+        }]
+    );
+    save_labels(&model.labels.decode(&predictions), prediction_file)?;
+    Ok(predictions)
+}
 
+/// Classifies the data file `batch_size` records at a time: every batch is read, encrypted,
+/// classified, decrypted and appended to the prediction file before the next one is read, so only
+/// one batch of ciphertexts is held in memory. With `resume`, the records already predicted in the
+/// prediction file are skipped. Returns the number of predictions in the file.
+pub fn classify_data_fhe_streaming(
+    data_file: &str,
+    model_file: &str,
+    prediction_file: &str,
+    options: &DatasetOptions,
+    mismatch: FeatureMismatch,
+    batch_size: usize,
+    resume: bool,
+) -> Result<usize, Error> {
     measure_duration!("1. Key Loading...",[
         let sk = if !EncryptKey::keys_exist(&PARAMS.gen_prefix()) {
             let key = EncryptKey::new();
@@ -57,33 +149,136 @@ pub fn classify_data_fhe(data_file: &str, model_file: &str, prediction_file: &st
             EncryptKey::load_from_files(&PARAMS.gen_prefix())
         };
     ]);
+    classify_batches_fhe(
+        &sk,
+        data_file,
+        model_file,
+        prediction_file,
+        options,
+        mismatch,
+        batch_size,
+        resume,
+    )
+}
+
+// Classifies the data file by batches with the key `sk`, see `classify_data_fhe_streaming`.
+#[allow(clippy::too_many_arguments)]
+fn classify_batches_fhe(
+    sk: &EncryptKey,
+    data_file: &str,
+    model_file: &str,
+    prediction_file: &str,
+    options: &DatasetOptions,
+    mismatch: FeatureMismatch,
+    batch_size: usize,
+    resume: bool,
+) -> Result<usize, Error> {
+    let model = Model::load(model_file)?;
+    let reader = reader_for(data_file, options);
+    let io_error = |e: std::io::Error| Error::Io(prediction_file.to_string(), e.to_string());
+    //Folding the scaling into the model so that the data is encrypted as it is
+    let (beta, mut constant) = model.scaling.fold(&model.beta);
+    constant += model.intercept.unwrap_or(0.);
+
+    //A first pass over the data file to plan the encoding, the characteristics unseen by the
+    //model are ignored by the planner
+    let mut parse_error: Option<ParseError> = None;
+    let rows = reader.records(data_file)?.map_while(|record| match record {
+        Ok((_, row)) => Some(row),
+        Err(e) => {
+            parse_error = Some(e);
+            None
+        }
+    });
+    let plan = EncodingPlan::for_classification(rows, &beta);
+    if let Some(e) = parse_error {
+        return Err(e.into());
+    }
+    plan.report();
+    let encoder = plan.encoder();
+
+    //A prediction cut by an interruption is dropped and classified again
+    let nb_done = if resume {
+        truncate_partial_line(prediction_file).map_err(io_error)?;
+        count_complete_lines(prediction_file).map_err(io_error)?
+    } else {
+        0
+    };
+    let mut predictions_file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resume)
+        .truncate(!resume)
+        .open(prediction_file)
+        .map_err(io_error)?;
+    if nb_done > 0 {
+        println!("Resuming after {} predicted records", nb_done);
+    }
+
     measure_duration!("2. Encryption... ",[
-        let e_x : CipherMatrix          = sk.encrypt_matrix(&x,-encoding_limit ,encoding_limit);
-        let e_beta : CipherVector       = sk.encrypt_vector(&beta,-encoding_limit,encoding_limit);
+        let e_beta : CipherVector       = sk.encrypt_vector_with_encoder(&beta, &encoder);
     ]);
+    let mut records = reader.records(data_file)?.skip(nb_done);
+    let mut nb_predicted = nb_done;
     measure_duration!(
-        "3. Classification... ",
-        [for i in 0..e_x.dim_n {
-            let xbeta = &e_x.get_row(i) * &e_beta;
-            let e_class = xbeta.bs_ks(|x| sigmoid(x));
-            let mut p_class = sk.decrypt_float(&e_class);
-            p_class = if p_class > 0. { 1. } else { -1. };
-            predictions.push(p_class);
+        "3. Streaming Classification... ",
+        [loop {
+            let mut batch: Vec<Vec<f64>> = Vec::new();
+            for record in records.by_ref().take(batch_size) {
+                let (_, row) = record?;
+                batch.push(row);
+            }
+            if batch.is_empty() {
+                break;
+            }
+            //Aligning the batch, its records are numbered from the start of the file
+            model.align(&mut batch, mismatch).map_err(|e| match e {
+                ModelError::UnseenFeature(record, index, nb_features) => {
+                    ModelError::UnseenFeature(record + nb_predicted, index, nb_features)
+                }
+                e => e,
+            })?;
+            let e_x : CipherMatrix = sk.encrypt_matrix_with_encoder(&batch, &encoder);
+            let mut predictions: Vec<f64> = Vec::new();
+            for i in 0..e_x.dim_n {
+                let xbeta = &e_x.get_row(i) * &e_beta;
+                let e_class = xbeta.bs_ks(|x| sigmoid(x + constant));
+                let p_class = sk.decrypt_float(&e_class);
+                predictions.push(if p_class > 0. { 1. } else { -1. });
+            }
+            for label in model.labels.decode(&predictions) {
+                writeln!(predictions_file, "{}", label).map_err(io_error)?;
+            }
+            predictions_file.sync_data().map_err(io_error)?;
+            nb_predicted += predictions.len();
+            println!("{} records classified", nb_predicted);
         }]
     );
-    save_vector(&predictions, prediction_file);
-    predictions
+    Ok(nb_predicted)
+}
+
+/// Counts the cipherfloat operations performed by `classify_data_fhe` on `n` records of dimension
+/// `d`.
+pub fn count_operations_fhe(n: usize, d: usize) -> OperationCount {
+    // x.beta followed by the sigmoid bootstrap
+    let record = OperationCount::dot_product(d)
+        + OperationCount {
+            functions: 1,
+            ..Default::default()
+        };
+    record * n as u64
 }
 
 //Sigmoid function with poly approximation
 fn sigmoid_classification(
     beta: &clear::vector::PlainVector,
     x: &clear::vector::PlainVector,
+    intercept: f64,
 ) -> (i32, f64) {
     let y = 1.;
     let threashold = 0.5;
-    let percent_y_1 = 1. / 2. + 1. / 4. * y * (beta * x);
-    let percent_y_neg_1 = 1. / 2. - 1. / 4. * y * (beta * x);
+    let percent_y_1 = 1. / 2. + 1. / 4. * y * (beta * x + intercept);
+    let percent_y_neg_1 = 1. / 2. - 1. / 4. * y * (beta * x + intercept);
     if percent_y_1 > threashold {
         (1, percent_y_1)
     } else {
@@ -102,3 +297,74 @@ fn sigmoid(x: f64) -> f64 {
         -1.
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reg::labels::LabelMapping;
+    use reg::scaling::Scaler;
+    use zqz::testing::temp_file;
+
+    #[test]
+    fn streaming_classification_resumes_after_a_partial_line() {
+        let data_file = temp_file("streaming_resume_data");
+        std::fs::write(&data_file, "1 1:2\n-1 2:2\n1 1:3\n").unwrap();
+        let model_file = temp_file("streaming_resume_model");
+        let model = Model::new(vec![1., -1.], LabelMapping::default(), Scaler::default());
+        model.save(&model_file).unwrap();
+        // The first record has been predicted, the second one was cut while being written. The
+        // prediction of the first record isn't the one of the model, to tell that it is kept
+        let prediction_file = temp_file("streaming_resume_predictions");
+        std::fs::write(&prediction_file, "-1\n-").unwrap();
+
+        let sk = EncryptKey::new_zero();
+        let options = DatasetOptions::default();
+        let nb_predicted = classify_batches_fhe(
+            &sk,
+            &data_file,
+            &model_file,
+            &prediction_file,
+            &options,
+            FeatureMismatch::Error,
+            2,
+            true,
+        )
+        .unwrap();
+
+        assert_eq!(nb_predicted, 3);
+        assert_eq!(
+            std::fs::read_to_string(&prediction_file).unwrap(),
+            "-1\n-1\n1\n"
+        );
+    }
+
+    #[test]
+    fn profiled_classification_matches_the_estimate() {
+        let (n, d) = (3, 2);
+        let data_file = temp_file("profiled_classification_data");
+        std::fs::write(&data_file, "1 1:2\n-1 2:2\n1 1:3 2:1\n").unwrap();
+        let model_file = temp_file("profiled_classification_model");
+        let model = Model::new(vec![1., -1.], LabelMapping::default(), Scaler::default());
+        model.save(&model_file).unwrap();
+
+        let sk = EncryptKey::new_zero();
+        let before = zqz::profile::counters();
+        classify_batches_fhe(
+            &sk,
+            &data_file,
+            &model_file,
+            &temp_file("profiled_classification_predictions"),
+            &DatasetOptions::default(),
+            FeatureMismatch::Error,
+            2,
+            false,
+        )
+        .unwrap();
+        let profiled = zqz::profile::counters() - before;
+
+        let estimate = count_operations_fhe(n, d);
+        assert_eq!(profiled.operations, estimate);
+        assert_eq!(profiled.bootstraps, estimate.bootstraps());
+        assert_eq!(profiled.keyswitches, estimate.keyswitches());
+    }
+}
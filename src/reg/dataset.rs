@@ -0,0 +1,261 @@
+//! A module reading the datasets from the different file formats.
+use crate::reg;
+
+use clap::ArgEnum;
+use reg::scaling::ScalingKind;
+use reg::utils::{collect_records, libsvm_records, ParseError, ParseErrorKind, ParseMode, Records};
+use std::path::Path;
+
+/// The file formats of the datasets.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum, Debug)]
+pub enum DatasetFormat {
+    /// label index:value index:value ...
+    Libsvm,
+    /// one record per line, with the label in one of the columns
+    Csv,
+}
+
+/// The options describing how to read a dataset.
+#[derive(Clone, PartialEq, Debug)]
+pub struct DatasetOptions {
+    // The format of the file, guessed from its extension when missing
+    pub format: Option<DatasetFormat>,
+    pub mode: ParseMode,
+    // Whether the first line of a csv file holds the names of the columns
+    pub has_header: bool,
+    // The index, or the name if there is a header, of the label column of a csv file
+    pub label_column: String,
+    pub delimiter: char,
+    // The labels of the classes 1 and -1, detected from the dataset when missing
+    pub positive_label: Option<String>,
+    pub negative_label: Option<String>,
+    // The scaling of the characteristics computed by the training
+    pub scaling: ScalingKind,
+}
+
+impl Default for DatasetOptions {
+    fn default() -> DatasetOptions {
+        DatasetOptions {
+            format: None,
+            mode: ParseMode::Strict,
+            has_header: false,
+            label_column: "0".to_string(),
+            delimiter: ',',
+            positive_label: None,
+            negative_label: None,
+            scaling: ScalingKind::None,
+        }
+    }
+}
+
+/// A reader turning a data file into the matrix of characteristics and the vector of labels.
+pub trait DatasetReader {
+    /// Reads the records of the data file one at a time.
+    fn records(&self, data_file: &str) -> Result<Records, ParseError>;
+
+    /// Reads all the records of the data file.
+    fn read(&self, data_file: &str) -> Result<(Vec<Vec<f64>>, Vec<String>), ParseError> {
+        collect_records(self.records(data_file)?)
+    }
+}
+
+/// A reader of the sparse libsvm format.
+pub struct LibsvmReader {
+    pub mode: ParseMode,
+}
+
+impl DatasetReader for LibsvmReader {
+    fn records(&self, data_file: &str) -> Result<Records, ParseError> {
+        libsvm_records(data_file, self.mode)
+    }
+}
+
+/// A reader of the dense csv format.
+pub struct CsvReader {
+    pub mode: ParseMode,
+    pub has_header: bool,
+    pub label_column: String,
+    pub delimiter: char,
+}
+
+// Finds the index of the label column, from its index or from its name in the header.
+fn label_index(label_column: &str, header: Option<&Vec<&str>>) -> Result<usize, ParseErrorKind> {
+    if let Ok(index) = label_column.parse::<usize>() {
+        return Ok(index);
+    }
+    header
+        .and_then(|names| names.iter().position(|name| *name == label_column))
+        .ok_or_else(|| ParseErrorKind::UnknownLabelColumn(label_column.to_string()))
+}
+
+impl DatasetReader for CsvReader {
+    fn records(&self, data_file: &str) -> Result<Records, ParseError> {
+        let has_header = self.has_header;
+        let label_column = self.label_column.clone();
+        let delimiter = self.delimiter;
+        // Both are found on the first line of the file
+        let mut label_column_index: Option<usize> = None;
+        let mut nb_columns: Option<usize> = None;
+        let parse = move |l: &str| {
+            if l.trim().is_empty() {
+                return Ok(None);
+            }
+            let cells: Vec<&str> = l.split(delimiter).map(|cell| cell.trim()).collect();
+            if label_column_index.is_none() {
+                let header = if has_header { Some(&cells) } else { None };
+                let index = label_index(&label_column, header)?;
+                if index >= cells.len() {
+                    return Err(ParseErrorKind::UnknownLabelColumn(label_column.clone()));
+                }
+                label_column_index = Some(index);
+                nb_columns = Some(cells.len());
+                if has_header {
+                    return Ok(None);
+                }
+            }
+            parse_record(&cells, label_column_index.unwrap(), nb_columns.unwrap()).map(Some)
+        };
+        Records::new(data_file, self.mode, Box::new(parse))
+    }
+}
+
+// Parses the cells of a csv record into its label and its row of characteristics.
+fn parse_record(
+    cells: &Vec<&str>,
+    label_index: usize,
+    nb_columns: usize,
+) -> Result<(String, Vec<f64>), ParseErrorKind> {
+    if cells.len() != nb_columns {
+        return Err(ParseErrorKind::InvalidColumnCount(nb_columns, cells.len()));
+    }
+    let mut class: String = String::new();
+    let mut row: Vec<f64> = Vec::new();
+    for (j, cell) in cells.iter().enumerate() {
+        if j == label_index {
+            class = cell.to_string();
+        } else {
+            let value: f64 = cell
+                .parse()
+                .map_err(|_| ParseErrorKind::InvalidValue(cell.to_string()))?;
+            row.push(value);
+        }
+    }
+    Ok((class, row))
+}
+
+/// Builds the reader of the data file, from the format of the options or the extension of the file.
+pub fn reader_for(data_file: &str, options: &DatasetOptions) -> Box<dyn DatasetReader> {
+    let format = options.format.unwrap_or_else(|| {
+        match Path::new(data_file).extension().and_then(|e| e.to_str()) {
+            Some("csv") => DatasetFormat::Csv,
+            _ => DatasetFormat::Libsvm,
+        }
+    });
+    match format {
+        DatasetFormat::Libsvm => Box::new(LibsvmReader { mode: options.mode }),
+        DatasetFormat::Csv => Box::new(CsvReader {
+            mode: options.mode,
+            has_header: options.has_header,
+            label_column: options.label_column.clone(),
+            delimiter: options.delimiter,
+        }),
+    }
+}
+
+/// Reads the data file with the reader matching the options.
+pub fn load_dataset(
+    data_file: &str,
+    options: &DatasetOptions,
+) -> Result<(Vec<Vec<f64>>, Vec<String>), ParseError> {
+    reader_for(data_file, options).read(data_file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zqz::testing::temp_file;
+
+    // Writes the content to a temporary data file and reads it with the options.
+    fn load(
+        name: &str,
+        content: &str,
+        options: &DatasetOptions,
+    ) -> Result<(Vec<Vec<f64>>, Vec<String>), ParseError> {
+        let path = temp_file(name);
+        std::fs::write(&path, content).unwrap();
+        load_dataset(&path, options)
+    }
+
+    fn labels(labels: &[&str]) -> Vec<String> {
+        labels.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn csv_label_column_by_index() {
+        let options = DatasetOptions {
+            format: Some(DatasetFormat::Csv),
+            label_column: "1".to_string(),
+            ..DatasetOptions::default()
+        };
+        let (x, y) = load("csv_index", "0.5,yes,2\n1, no ,-1\n\n", &options).unwrap();
+        assert_eq!(x, vec![vec![0.5, 2.], vec![1., -1.]]);
+        assert_eq!(y, labels(&["yes", "no"]));
+    }
+
+    #[test]
+    fn csv_header_and_delimiter() {
+        let options = DatasetOptions {
+            has_header: true,
+            label_column: "default".to_string(),
+            delimiter: ';',
+            ..DatasetOptions::default()
+        };
+        let (x, y) = load("dataset.csv", "a;b;default\n1;2;0\n3;4;1\n", &options).unwrap();
+        assert_eq!(x, vec![vec![1., 2.], vec![3., 4.]]);
+        assert_eq!(y, labels(&["0", "1"]));
+    }
+
+    #[test]
+    fn csv_unknown_label_column() {
+        let options = DatasetOptions {
+            format: Some(DatasetFormat::Csv),
+            has_header: true,
+            label_column: "class".to_string(),
+            ..DatasetOptions::default()
+        };
+        let error = load("csv_unknown", "a,b\n1,2\n", &options).unwrap_err();
+        assert_eq!(error.line, 1);
+        assert_eq!(
+            error.kind,
+            ParseErrorKind::UnknownLabelColumn("class".to_string())
+        );
+    }
+
+    #[test]
+    fn csv_missing_values() {
+        let content = "1,0.5,2\n-1,,3\n1,4\n-1,1,1\n";
+        let options = DatasetOptions {
+            format: Some(DatasetFormat::Csv),
+            ..DatasetOptions::default()
+        };
+        let error = load("csv_missing_strict", content, &options).unwrap_err();
+        assert_eq!(error.line, 2);
+        assert_eq!(error.kind, ParseErrorKind::InvalidValue("".to_string()));
+
+        // The empty cell and the short record are skipped
+        let options = DatasetOptions {
+            mode: ParseMode::Lenient,
+            ..options
+        };
+        let (x, y) = load("csv_missing_lenient", content, &options).unwrap();
+        assert_eq!(x, vec![vec![0.5, 2.], vec![1., 1.]]);
+        assert_eq!(y, labels(&["1", "-1"]));
+    }
+
+    #[test]
+    fn libsvm_is_the_default_format() {
+        let (x, y) = load("dataset.txt", "1 2:1\n", &DatasetOptions::default()).unwrap();
+        assert_eq!(x, vec![vec![0., 1.]]);
+        assert_eq!(y, labels(&["1"]));
+    }
+}
@@ -0,0 +1,69 @@
+//! A module containing the errors of the regression.
+use crate::reg;
+
+use reg::checkpoint::CheckpointError;
+use reg::labels::LabelError;
+use reg::model::ModelError;
+use reg::planner::PlanError;
+use reg::utils::ParseError;
+use std::fmt;
+
+/// An error stopping a training, a classification or an accuracy computation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    Parse(ParseError),
+    Model(ModelError),
+    Label(LabelError),
+    Checkpoint(CheckpointError),
+    Plan(PlanError),
+    // The file could not be read or written
+    Io(String, String),
+    // The data file has no records
+    EmptyDataset(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Parse(e) => write!(f, "{}", e),
+            Error::Model(e) => write!(f, "{}", e),
+            Error::Label(e) => write!(f, "{}", e),
+            Error::Checkpoint(e) => write!(f, "{}", e),
+            Error::Plan(e) => write!(f, "{}", e),
+            Error::Io(file, e) => write!(f, "{}: {}", file, e),
+            Error::EmptyDataset(file) => write!(f, "{}: the dataset has no records", file),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Error {
+        Error::Parse(e)
+    }
+}
+
+impl From<ModelError> for Error {
+    fn from(e: ModelError) -> Error {
+        Error::Model(e)
+    }
+}
+
+impl From<LabelError> for Error {
+    fn from(e: LabelError) -> Error {
+        Error::Label(e)
+    }
+}
+
+impl From<CheckpointError> for Error {
+    fn from(e: CheckpointError) -> Error {
+        Error::Checkpoint(e)
+    }
+}
+
+impl From<PlanError> for Error {
+    fn from(e: PlanError) -> Error {
+        Error::Plan(e)
+    }
+}
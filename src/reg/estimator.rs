@@ -0,0 +1,92 @@
+//! A module estimating the cost of the encrypted training and classification without running them.
+use crate::reg;
+use crate::zqz;
+
+use reg::dataset::{load_dataset, DatasetOptions};
+use reg::error::Error;
+use reg::model::Model;
+
+use crate::measure_duration;
+use crate::PARAMS;
+
+use concrete::crypto_api;
+use zqz::cost::{ciphertext_size, keys_size, OperationCount};
+use zqz::keys::EncryptKey;
+
+// Number of bootstraps timed to estimate the duration of one bootstrap.
+const NBR_SAMPLE_BOOTSTRAPS: u32 = 5;
+
+/// Estimates the cost of `train_data_fhe` on the data file.
+pub fn estimate_training_fhe(
+    data_file: &str,
+    options: &DatasetOptions,
+) -> Result<(), Error> {
+    let (x, _) = load_dataset(&data_file, options)?;
+    if x.is_empty() {
+        return Err(Error::EmptyDataset(data_file.to_string()));
+    }
+    let n = x.len();
+    let d = x[0].len();
+    let operations = reg::trainer::count_operations_fhe(n, d);
+    // x and its transpose, y, a, g and beta, the inverse hessian stays in clear
+    let nbr_ciphertexts = (2 * n * d + 2 * n + 2 * d) as u64;
+    report(&operations, nbr_ciphertexts);
+    Ok(())
+}
+
+/// Estimates the cost of `classify_data_fhe` on the data file with the model.
+pub fn estimate_classification_fhe(
+    data_file: &str,
+    model_file: &str,
+    options: &DatasetOptions,
+) -> Result<(), Error> {
+    let (x, _) = load_dataset(&data_file, options)?;
+    let model = Model::load(model_file)?;
+    let n = x.len();
+    let d = model.nb_features;
+    let operations = reg::classifier::count_operations_fhe(n, d);
+    // x and beta
+    let nbr_ciphertexts = (n * d + d) as u64;
+    report(&operations, nbr_ciphertexts);
+    Ok(())
+}
+
+// Times a few bootstraps and returns the mean duration in seconds. The keys of the parameters are
+// used if they exist, otherwise throwaway keys are generated and never saved, a dry run doesn't
+// write any file.
+fn time_bootstrap() -> f64 {
+    measure_duration!("Key Loading...",[
+        let sk = if EncryptKey::keys_exist(&PARAMS.gen_prefix()) {
+            EncryptKey::load_from_files(&PARAMS.gen_prefix())
+        } else {
+            EncryptKey::new()
+        };
+    ]);
+    let encoder: crypto_api::Encoder =
+        crypto_api::Encoder::new(-1., 1., PARAMS.nb_bit_precision, PARAMS.nb_bit_padding).unwrap();
+    let e_sample = sk.encrypt_float_with_encoder(0.5, &encoder);
+    let now = std::time::SystemTime::now();
+    for _ in 0..NBR_SAMPLE_BOOTSTRAPS {
+        e_sample.clone().bs_ks(|x| x);
+    }
+    now.elapsed().unwrap().as_secs_f64() / NBR_SAMPLE_BOOTSTRAPS as f64
+}
+
+fn report(operations: &OperationCount, nbr_ciphertexts: u64) {
+    let bootstrap_duration = time_bootstrap();
+    let duration = operations.bootstraps() as f64 * bootstrap_duration;
+    let memory = keys_size() + nbr_ciphertexts * ciphertext_size();
+    println!("Dry run:");
+    println!("  additions: {}", operations.additions);
+    println!("  multiplications: {}", operations.multiplications);
+    println!("  constant multiplications: {}", operations.mul_constants);
+    println!("  function evaluations: {}", operations.functions);
+    println!("  bootstraps: {}", operations.bootstraps());
+    println!("  keyswitches: {}", operations.keyswitches());
+    println!("  bootstrap duration: {} s", bootstrap_duration);
+    println!("  estimated duration: {} s ({} h)", duration, duration / 3600.);
+    println!(
+        "  estimated memory: {} MB",
+        memory as f64 / (1024. * 1024.)
+    );
+}
@@ -0,0 +1,219 @@
+//! A module exchanging models with other tools, such as scikit-learn, as JSON.
+use crate::reg;
+
+use reg::dataset::DatasetOptions;
+use reg::error::Error;
+use reg::labels::LabelMapping;
+use reg::model::{Model, ModelError, TrainingInfo, FORMAT_VERSION};
+use reg::scaling::Scaler;
+use serde::Serialize;
+use serde_json::Value;
+
+/// The formats models can be exported to.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum ExportFormat {
+    // Coefficients, intercept and metadata, named after scikit-learn's attributes
+    Json,
+}
+
+// The exported model, `coef`, `intercept` and `classes` follow scikit-learn's `LogisticRegression`.
+#[derive(Serialize)]
+struct ExportedModel<'a> {
+    format_version: u32,
+    coef: &'a Vec<f64>,
+    intercept: f64,
+    // The negative label followed by the positive one
+    classes: [&'a str; 2],
+    nb_features: usize,
+    scaling: &'a Scaler,
+    training: &'a Option<TrainingInfo>,
+}
+
+/// Exports the model to the output file, or to the standard output if there is none.
+pub fn export_model(
+    model_file: &str,
+    output_file: Option<&str>,
+    format: ExportFormat,
+) -> Result<(), Error> {
+    let model = Model::load(model_file)?;
+    let exported = match format {
+        ExportFormat::Json => serde_json::to_string_pretty(&ExportedModel {
+            format_version: FORMAT_VERSION,
+            coef: &model.beta,
+            intercept: model.intercept.unwrap_or(0.),
+            classes: [model.labels.negative.as_str(), model.labels.positive.as_str()],
+            nb_features: model.nb_features,
+            scaling: &model.scaling,
+            training: &model.training,
+        })
+        .unwrap(),
+    };
+    match output_file {
+        Some(output_file) => std::fs::write(output_file, exported)
+            .map_err(|e| ModelError::Io(output_file.to_string(), e.to_string()))?,
+        None => println!("{}", exported),
+    }
+    Ok(())
+}
+
+/// Imports the coefficients of a JSON file, such as the attributes of a scikit-learn
+/// `LogisticRegression`, into a model file.
+pub fn import_model(
+    json_file: &str,
+    model_file: &str,
+    options: &DatasetOptions,
+) -> Result<Model, Error> {
+    let invalid = |reason: &str| ModelError::Invalid(json_file.to_string(), reason.to_string());
+    let content = std::fs::read_to_string(json_file)
+        .map_err(|e| ModelError::Io(json_file.to_string(), e.to_string()))?;
+    let json: Value = serde_json::from_str(&content).map_err(|e| invalid(&e.to_string()))?;
+
+    let coef = json
+        .get("coef")
+        .or_else(|| json.get("coef_"))
+        .ok_or_else(|| invalid("missing coef"))?;
+    // scikit-learn stores the coefficients of a binary model as a matrix of one row
+    let coef = match coef.as_array().map(|rows| rows.as_slice()) {
+        Some([row @ Value::Array(_)]) => row,
+        Some(rows) if rows.len() > 1 && rows[0].is_array() => {
+            return Err(invalid("only binary models of a single row of coef are supported").into())
+        }
+        _ => coef,
+    };
+    let beta: Vec<f64> = coef
+        .as_array()
+        .and_then(|values| values.iter().map(|value| value.as_f64()).collect())
+        .ok_or_else(|| invalid("coef isn't an array of numbers"))?;
+
+    let intercept = match json.get("intercept").or_else(|| json.get("intercept_")) {
+        None | Some(Value::Null) => None,
+        Some(value) => {
+            // scikit-learn stores the intercept of a binary model as an array of one number
+            let value = match value {
+                Value::Array(values) if values.len() == 1 => &values[0],
+                value => value,
+            };
+            Some(
+                value
+                    .as_f64()
+                    .ok_or_else(|| invalid("intercept isn't a number"))?,
+            )
+        }
+    };
+
+    let labels = match json.get("classes").or_else(|| json.get("classes_")) {
+        Some(Value::Array(classes)) if classes.len() == 2 => {
+            let label = |class: &Value| match class {
+                Value::String(label) => label.clone(),
+                other => other.to_string(),
+            };
+            LabelMapping {
+                positive: label(&classes[1]),
+                negative: label(&classes[0]),
+                others_negative: false,
+                others_positive: false,
+            }
+        }
+        Some(_) => return Err(invalid("classes isn't an array of two labels").into()),
+        None => LabelMapping::from_options(&Vec::new(), options)?,
+    };
+
+    let scaling = match json.get("scaling") {
+        Some(scaling) => serde_json::from_value(scaling.clone())
+            .map_err(|_| invalid("scaling isn't a valid scaling"))?,
+        None => Scaler::default(),
+    };
+
+    let mut model = Model::new(beta, labels, scaling);
+    model.intercept = intercept;
+    model.save(model_file)?;
+    // Loading the model back checks that its parts agree with each other
+    Ok(Model::load(model_file)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zqz::testing::temp_file;
+
+    // Writes the JSON to a temporary file and imports it.
+    fn import(name: &str, json: &str) -> Result<Model, Error> {
+        let json_file = temp_file(&format!("{}.json", name));
+        std::fs::write(&json_file, json).unwrap();
+        import_model(&json_file, &temp_file(name), &DatasetOptions::default())
+    }
+
+    // Checks that the import of the JSON fails for the reason.
+    fn assert_invalid(name: &str, json: &str, reason: &str) {
+        let json_file = temp_file(&format!("{}.json", name));
+        assert_eq!(
+            import(name, json),
+            Err(Error::Model(ModelError::Invalid(json_file, reason.to_string())))
+        );
+    }
+
+    #[test]
+    fn export_and_import_models() {
+        let labels = LabelMapping {
+            positive: "yes".to_string(),
+            negative: "no".to_string(),
+            others_negative: false,
+            others_positive: false,
+        };
+        let mut model = Model::new(vec![0.5, -1.25, 2.], labels, Scaler::default());
+        model.intercept = Some(0.75);
+        let model_file = temp_file("interop_exported_model");
+        let json_file = temp_file("interop_exported_model.json");
+        model.save(&model_file).unwrap();
+
+        export_model(&model_file, Some(&json_file), ExportFormat::Json).unwrap();
+        let imported_file = temp_file("interop_imported_model");
+        let options = DatasetOptions::default();
+        let imported = import_model(&json_file, &imported_file, &options).unwrap();
+        assert_eq!(imported.beta, model.beta);
+        assert_eq!(imported.intercept, model.intercept);
+        assert_eq!(imported.labels, model.labels);
+        assert_eq!(imported.scaling, model.scaling);
+        assert_eq!(imported.nb_features, 3);
+    }
+
+    #[test]
+    fn import_scikit_learn_attributes() {
+        let model = import(
+            "interop_sklearn",
+            r#"{"coef_": [[1.5, -2]], "intercept_": [0.25], "classes_": [0, 1]}"#,
+        )
+        .unwrap();
+        assert_eq!(model.beta, vec![1.5, -2.]);
+        assert_eq!(model.intercept, Some(0.25));
+        assert_eq!((model.labels.negative.as_str(), model.labels.positive.as_str()), ("0", "1"));
+    }
+
+    #[test]
+    fn import_without_intercept() {
+        let model = import("interop_no_intercept", r#"{"coef": [1, 2], "intercept": null}"#);
+        assert_eq!(model.unwrap().intercept, None);
+    }
+
+    #[test]
+    fn reject_malformed_intercepts() {
+        let reason = "intercept isn't a number";
+        assert_invalid("interop_text", r#"{"coef": [1], "intercept": ["a"]}"#, reason);
+        assert_invalid("interop_two", r#"{"coef": [1], "intercept": [1, 2]}"#, reason);
+        assert_invalid("interop_string", r#"{"coef": [1], "intercept": "1"}"#, reason);
+    }
+
+    #[test]
+    fn reject_multiclass_and_malformed_coefficients() {
+        assert_invalid(
+            "interop_multiclass",
+            r#"{"coef": [[1, 2], [3, 4]]}"#,
+            "only binary models of a single row of coef are supported",
+        );
+        assert_invalid(
+            "interop_text_coef",
+            r#"{"coef": [1, "a"]}"#,
+            "coef isn't an array of numbers",
+        );
+    }
+}
@@ -0,0 +1,257 @@
+//! A module mapping the labels of a binary dataset to the classes 1 and -1 of the regression.
+use crate::reg;
+
+use reg::dataset::DatasetOptions;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+
+/// The problems that can be found while mapping the labels of a dataset.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LabelError {
+    // The dataset has more than two distinct labels
+    TooManyClasses(Vec<String>),
+    // The label is neither the positive nor the negative one
+    UnknownLabel(String),
+}
+
+impl fmt::Display for LabelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LabelError::TooManyClasses(labels) => write!(
+                f,
+                "the dataset has more than two classes: {}",
+                labels.join(", ")
+            ),
+            LabelError::UnknownLabel(label) => write!(
+                f,
+                "the label '{}' is neither the positive nor the negative label",
+                label
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LabelError {}
+
+/// Compares two labels, numeric labels are equal if their values are, so that `+1` matches `1`.
+pub fn same_label(a: &str, b: &str) -> bool {
+    if a == b {
+        return true;
+    }
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// The labels of the dataset standing for the classes 1 and -1.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LabelMapping {
+    pub positive: String,
+    pub negative: String,
+    // Whether the labels other than the positive one are accepted as negative
+    pub others_negative: bool,
+    // Whether the labels other than the negative one are accepted as positive
+    #[serde(default)]
+    pub others_positive: bool,
+}
+
+impl Default for LabelMapping {
+    fn default() -> LabelMapping {
+        LabelMapping {
+            positive: "1".to_string(),
+            negative: "-1".to_string(),
+            others_negative: true,
+            others_positive: false,
+        }
+    }
+}
+
+impl LabelMapping {
+    /// Builds the mapping from the labels given in the options, the missing ones are detected
+    /// from the labels of the dataset. When only one of the labels is given, every other label
+    /// belongs to the opposite class.
+    pub fn from_options(
+        labels: &Vec<String>,
+        options: &DatasetOptions,
+    ) -> Result<LabelMapping, LabelError> {
+        // The distinct labels, in their order of appearance
+        let mut classes: Vec<String> = Vec::new();
+        for label in labels {
+            if !classes.iter().any(|class| same_label(class, label)) {
+                classes.push(label.clone());
+            }
+        }
+        let other = |label: &str| classes.iter().find(|class| !same_label(class, label)).cloned();
+        match (&options.positive_label, &options.negative_label) {
+            (Some(positive), Some(negative)) => Ok(LabelMapping {
+                positive: positive.clone(),
+                negative: negative.clone(),
+                others_negative: false,
+                others_positive: false,
+            }),
+            // Every label but the positive one is negative
+            (Some(positive), None) => Ok(LabelMapping {
+                positive: positive.clone(),
+                negative: other(positive).unwrap_or_else(|| "-1".to_string()),
+                others_negative: true,
+                others_positive: false,
+            }),
+            // Every label but the negative one is positive
+            (None, Some(negative)) => Ok(LabelMapping {
+                positive: other(negative).unwrap_or_else(|| "1".to_string()),
+                negative: negative.clone(),
+                others_negative: false,
+                others_positive: true,
+            }),
+            (None, None) => LabelMapping::detect(classes.clone()),
+        }
+    }
+
+    // Detects the mapping from the distinct labels of the dataset, the greatest one is positive.
+    fn detect(mut classes: Vec<String>) -> Result<LabelMapping, LabelError> {
+        if classes.len() > 2 {
+            return Err(LabelError::TooManyClasses(classes));
+        }
+        classes.sort_by(|a, b| match (a.parse::<f64>(), b.parse::<f64>()) {
+            (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+            _ => a.cmp(b),
+        });
+        let (positive, negative) = match classes.len() {
+            2 => (classes[1].clone(), classes[0].clone()),
+            // A dataset with a single class, the other one is guessed
+            1 => match classes[0].parse::<f64>() {
+                Ok(class) if class > 0. => (classes[0].clone(), "-1".to_string()),
+                _ => ("1".to_string(), classes[0].clone()),
+            },
+            _ => ("1".to_string(), "-1".to_string()),
+        };
+        Ok(LabelMapping {
+            positive,
+            negative,
+            others_negative: false,
+            others_positive: false,
+        })
+    }
+
+    /// Maps the labels to the classes 1 and -1.
+    pub fn encode(&self, labels: &Vec<String>) -> Result<Vec<f64>, LabelError> {
+        let mut y: Vec<f64> = Vec::new();
+        for label in labels {
+            if same_label(label, &self.positive) {
+                y.push(1.);
+            } else if same_label(label, &self.negative) || self.others_negative {
+                y.push(-1.);
+            } else if self.others_positive {
+                y.push(1.);
+            } else {
+                return Err(LabelError::UnknownLabel(label.clone()));
+            }
+        }
+        Ok(y)
+    }
+
+    /// Maps the classes back to the labels of the dataset.
+    pub fn decode(&self, y: &Vec<f64>) -> Vec<String> {
+        y.iter()
+            .map(|class| {
+                if *class > 0. {
+                    self.positive.clone()
+                } else {
+                    self.negative.clone()
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(labels: &[&str]) -> Vec<String> {
+        labels.iter().map(|l| l.to_string()).collect()
+    }
+
+    fn options(positive: Option<&str>, negative: Option<&str>) -> DatasetOptions {
+        DatasetOptions {
+            positive_label: positive.map(|l| l.to_string()),
+            negative_label: negative.map(|l| l.to_string()),
+            ..DatasetOptions::default()
+        }
+    }
+
+    #[test]
+    fn numeric_labels_are_compared_by_value() {
+        assert!(same_label("+1", "1"));
+        assert!(same_label("1.0", "1"));
+        assert!(!same_label("1", "-1"));
+        assert!(!same_label("yes", "Yes"));
+    }
+
+    #[test]
+    fn detect_the_greatest_label_as_positive() {
+        let mapping = LabelMapping::from_options(&labels(&["0", "1", "0"]), &options(None, None));
+        let mapping = mapping.unwrap();
+        assert_eq!((mapping.positive.as_str(), mapping.negative.as_str()), ("1", "0"));
+        // Numeric labels are sorted by value, not as strings
+        let mapping = LabelMapping::from_options(&labels(&["10", "9"]), &options(None, None));
+        assert_eq!(mapping.unwrap().positive, "10");
+        let mapping = LabelMapping::from_options(&labels(&["yes", "no"]), &options(None, None));
+        assert_eq!(mapping.unwrap().positive, "yes");
+    }
+
+    #[test]
+    fn detect_a_single_class() {
+        let mapping = LabelMapping::from_options(&labels(&["2"]), &options(None, None)).unwrap();
+        assert_eq!((mapping.positive.as_str(), mapping.negative.as_str()), ("2", "-1"));
+        let mapping = LabelMapping::from_options(&labels(&["0"]), &options(None, None)).unwrap();
+        assert_eq!((mapping.positive.as_str(), mapping.negative.as_str()), ("1", "0"));
+    }
+
+    #[test]
+    fn reject_more_than_two_classes() {
+        assert_eq!(
+            LabelMapping::from_options(&labels(&["a", "b", "c"]), &options(None, None)),
+            Err(LabelError::TooManyClasses(labels(&["a", "b", "c"])))
+        );
+    }
+
+    #[test]
+    fn both_labels_reject_the_others() {
+        let dataset = labels(&["a", "b", "c"]);
+        let mapping = LabelMapping::from_options(&dataset, &options(Some("a"), Some("b"))).unwrap();
+        assert_eq!(
+            mapping.encode(&dataset),
+            Err(LabelError::UnknownLabel("c".to_string()))
+        );
+        assert_eq!(mapping.encode(&labels(&["b", "a"])), Ok(vec![-1., 1.]));
+    }
+
+    #[test]
+    fn others_are_the_opposite_of_the_given_label() {
+        let dataset = labels(&["a", "b", "c"]);
+        let mapping = LabelMapping::from_options(&dataset, &options(Some("b"), None)).unwrap();
+        assert_eq!(mapping.negative, "a");
+        assert_eq!(mapping.encode(&dataset), Ok(vec![-1., 1., -1.]));
+
+        let mapping = LabelMapping::from_options(&dataset, &options(None, Some("b"))).unwrap();
+        assert_eq!(mapping.positive, "a");
+        assert_eq!(mapping.encode(&dataset), Ok(vec![1., -1., 1.]));
+    }
+
+    #[test]
+    fn decode_the_classes() {
+        let mapping = LabelMapping::from_options(&labels(&["no", "yes"]), &options(None, None));
+        let mapping = mapping.unwrap();
+        assert_eq!(mapping.decode(&vec![1., -1., 0.3]), labels(&["yes", "no", "yes"]));
+    }
+
+    #[test]
+    fn older_mappings_accept_no_other_positive_labels() {
+        let json = r#"{"positive":"1","negative":"0","others_negative":false}"#;
+        let mapping: LabelMapping = serde_json::from_str(json).unwrap();
+        assert!(!mapping.others_positive);
+    }
+}
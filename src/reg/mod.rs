@@ -1,4 +1,14 @@
 pub mod utils;
+pub mod dataset;
+pub mod error;
+pub mod labels;
+pub mod model;
+pub mod scaling;
 pub mod classifier;
+pub mod checkpoint;
 pub mod trainer;
-pub mod accuracy;
\ No newline at end of file
+pub mod accuracy;
+pub mod planner;
+pub mod estimator;
+pub mod interop;
+pub mod replay;
\ No newline at end of file
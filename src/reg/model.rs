@@ -0,0 +1,271 @@
+//! A module containing the model produced by the training.
+use crate::reg;
+
+use clap::ArgEnum;
+use reg::labels::{same_label, LabelMapping};
+use reg::scaling::{Scaler, ScalingKind};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// The first bytes of a model file, telling it apart from the bare vectors of the first versions.
+const MAGIC: &[u8; 8] = b"FHELRMDL";
+/// The version of the model file format written by this program.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The problems that can be found while loading or applying a model.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModelError {
+    // The file could not be read or written
+    Io(String, String),
+    // The file doesn't hold a model
+    Corrupted(String),
+    // The model was written by a newer version of the program
+    UnsupportedVersion(String, u32),
+    // The model is inconsistent
+    Invalid(String, String),
+    // A record has a non zero value for a characteristic the model hasn't been trained on
+    UnseenFeature(usize, usize, usize),
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ModelError::Io(file, e) => write!(f, "{}: could not access the model: {}", file, e),
+            ModelError::Corrupted(file) => write!(f, "{}: not a model file", file),
+            ModelError::UnsupportedVersion(file, version) => write!(
+                f,
+                "{}: model format version {} is newer than the supported version {}",
+                file, version, FORMAT_VERSION
+            ),
+            ModelError::Invalid(file, reason) => write!(f, "{}: invalid model: {}", file, reason),
+            ModelError::UnseenFeature(record, index, nb_features) => write!(
+                f,
+                "record {} has a value for the characteristic {} but the model only has {}",
+                record, index, nb_features
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {}
+
+/// What to do with the records having values for characteristics unseen by the model.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum, Debug)]
+pub enum FeatureMismatch {
+    /// drop the unseen characteristics with a warning
+    Warn,
+    /// stop with an error
+    Error,
+}
+
+/// How the model has been trained.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrainingInfo {
+    pub algorithm: String,
+    pub nbr_iters: usize,
+    pub initial_beta: f64,
+    // The FNV-1a hash of the training data file
+    pub dataset_hash: String,
+    // The prefix of the keys files if the model has been trained encrypted
+    pub fhe_prefix: Option<String>,
+}
+
+/// The trained model, with the number of characteristics, the labels and the scaling of the data
+/// it was trained on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Model {
+    pub nb_features: usize,
+    pub beta: Vec<f64>,
+    pub intercept: Option<f64>,
+    pub labels: LabelMapping,
+    pub scaling: Scaler,
+    pub training: Option<TrainingInfo>,
+}
+
+impl Model {
+    pub fn new(beta: Vec<f64>, labels: LabelMapping, scaling: Scaler) -> Model {
+        Model {
+            nb_features: beta.len(),
+            beta,
+            intercept: None,
+            labels,
+            scaling,
+            training: None,
+        }
+    }
+
+    /// Saves the model to a file, after the header holding the format version
+    pub fn save(&self, filename: &str) -> Result<(), ModelError> {
+        let mut encoded: Vec<u8> = MAGIC.to_vec();
+        encoded.extend(bincode::serialize(&FORMAT_VERSION).unwrap());
+        encoded.extend(bincode::serialize(&self).unwrap());
+        std::fs::write(filename, &encoded)
+            .map_err(|e| ModelError::Io(filename.to_string(), e.to_string()))
+    }
+
+    /// Loads and validates the model from a file, the models saved as a bare vector of
+    /// coefficients are accepted
+    pub fn load(filename: &str) -> Result<Model, ModelError> {
+        let data = std::fs::read(filename)
+            .map_err(|e| ModelError::Io(filename.to_string(), e.to_string()))?;
+        if !data.starts_with(MAGIC) {
+            return match bincode::deserialize::<Vec<f64>>(&data[..]) {
+                Ok(beta) => Ok(Model::new(beta, LabelMapping::default(), Scaler::default())),
+                Err(_) => Err(ModelError::Corrupted(filename.to_string())),
+            };
+        }
+        let header = &data[MAGIC.len()..];
+        let version: u32 = bincode::deserialize(header)
+            .map_err(|_| ModelError::Corrupted(filename.to_string()))?;
+        if version > FORMAT_VERSION {
+            return Err(ModelError::UnsupportedVersion(filename.to_string(), version));
+        }
+        let body = &header[bincode::serialized_size(&version).unwrap() as usize..];
+        let model: Model = bincode::deserialize(body)
+            .map_err(|_| ModelError::Corrupted(filename.to_string()))?;
+        model
+            .validate()
+            .map_err(|reason| ModelError::Invalid(filename.to_string(), reason))?;
+        Ok(model)
+    }
+
+    // Checks that the parts of the model agree with each other.
+    fn validate(&self) -> Result<(), String> {
+        if self.beta.len() != self.nb_features {
+            return Err(format!(
+                "{} coefficients for {} characteristics",
+                self.beta.len(),
+                self.nb_features
+            ));
+        }
+        if self.scaling.kind != ScalingKind::None
+            && (self.scaling.offsets.len() != self.nb_features
+                || self.scaling.scales.len() != self.nb_features)
+        {
+            return Err(format!(
+                "scaling of {} characteristics for {} characteristics",
+                self.scaling.scales.len(),
+                self.nb_features
+            ));
+        }
+        if same_label(&self.labels.positive, &self.labels.negative) {
+            return Err(format!(
+                "the positive and negative labels are both '{}'",
+                self.labels.positive
+            ));
+        }
+        Ok(())
+    }
+
+    /// Aligns the rows of `x` to the characteristics of the model, the missing trailing
+    /// characteristics are filled with 0 and the unseen ones are dropped or rejected.
+    pub fn align(
+        &self,
+        x: &mut Vec<Vec<f64>>,
+        mismatch: FeatureMismatch,
+    ) -> Result<(), ModelError> {
+        let mut nb_unseen: usize = 0;
+        for i in 0..x.len() {
+            // Rows are squared up to the longest one of the file, so trailing zeros aren't unseen
+            if let Some(j) = (self.nb_features..x[i].len()).find(|j| x[i][*j] != 0.) {
+                match mismatch {
+                    FeatureMismatch::Error => {
+                        return Err(ModelError::UnseenFeature(i + 1, j + 1, self.nb_features))
+                    }
+                    FeatureMismatch::Warn => nb_unseen += 1,
+                }
+            }
+            x[i].resize(self.nb_features, 0.);
+        }
+        if nb_unseen > 0 {
+            println!(
+                "Warning: dropped the characteristics unseen by the model in {} records",
+                nb_unseen
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reg::utils::{parse_data_file, ParseMode};
+    use crate::zqz::testing::temp_file;
+
+    // Writes the content to a temporary data file and parses it.
+    fn parse(name: &str, content: &str) -> Vec<Vec<f64>> {
+        let path = temp_file(name);
+        std::fs::write(&path, content).unwrap();
+        let (x, _) = parse_data_file(&path, ParseMode::Strict).unwrap();
+        x
+    }
+
+    #[test]
+    fn align_pads_shorter_rows() {
+        let beta = parse("align_train_pad", "1 1:1 4:1\n-1 2:1\n").remove(0);
+        let model = Model::new(beta, LabelMapping::default(), Scaler::default());
+        let mut x = parse("align_dev_pad", "1 1:1\n-1 2:1\n");
+        assert_eq!(x[0].len(), 2);
+        model.align(&mut x, FeatureMismatch::Error).unwrap();
+        assert_eq!(x, vec![vec![1., 0., 0., 0.], vec![0., 1., 0., 0.]]);
+    }
+
+    #[test]
+    fn align_drops_trailing_zeros() {
+        let model = Model::new(vec![0.5, 0.5], LabelMapping::default(), Scaler::default());
+        let mut x = parse("align_dev_zeros", "1 1:1\n-1 4:0\n");
+        model.align(&mut x, FeatureMismatch::Error).unwrap();
+        assert_eq!(x, vec![vec![1., 0.], vec![0., 0.]]);
+    }
+
+    #[test]
+    fn align_rejects_unseen_features() {
+        let model = Model::new(vec![0.5, 0.5], LabelMapping::default(), Scaler::default());
+        let mut x = parse("align_dev_unseen", "1 1:1\n-1 2:1 3:1\n");
+        assert_eq!(
+            model.align(&mut x, FeatureMismatch::Error),
+            Err(ModelError::UnseenFeature(2, 3, 2))
+        );
+    }
+
+    #[test]
+    fn align_warns_on_unseen_features() {
+        let model = Model::new(vec![0.5, 0.5], LabelMapping::default(), Scaler::default());
+        let mut x = parse("align_dev_warn", "1 1:1\n-1 2:1 3:1\n");
+        model.align(&mut x, FeatureMismatch::Warn).unwrap();
+        assert_eq!(x, vec![vec![1., 0.], vec![0., 1.]]);
+    }
+
+    #[test]
+    fn save_and_load_models() {
+        let path = temp_file("versioned_model");
+        let mut model = Model::new(vec![0.25, -1.5], LabelMapping::default(), Scaler::default());
+        model.intercept = Some(0.5);
+        model.save(&path).unwrap();
+        assert_eq!(Model::load(&path).unwrap(), model);
+    }
+
+    #[test]
+    fn reject_newer_models() {
+        let path = temp_file("newer_model");
+        let model = Model::new(vec![0.25, -1.5], LabelMapping::default(), Scaler::default());
+        let mut encoded: Vec<u8> = MAGIC.to_vec();
+        encoded.extend(bincode::serialize(&(FORMAT_VERSION + 1)).unwrap());
+        encoded.extend(bincode::serialize(&model).unwrap());
+        std::fs::write(&path, encoded).unwrap();
+        assert_eq!(
+            Model::load(&path),
+            Err(ModelError::UnsupportedVersion(path.clone(), FORMAT_VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn load_bare_vector_models() {
+        let path = temp_file("bare_vector_model");
+        let beta: Vec<f64> = vec![0.25, -1.5, 3.];
+        std::fs::write(&path, bincode::serialize(&beta).unwrap()).unwrap();
+        let model = Model::load(&path).unwrap();
+        assert_eq!(model, Model::new(beta, LabelMapping::default(), Scaler::default()));
+    }
+}
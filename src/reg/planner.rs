@@ -0,0 +1,270 @@
+//! A module choosing the encoding parameters from the statistics of the data and the model.
+use crate::PARAMS;
+use concrete::crypto_api;
+use std::fmt;
+
+// Factor applied on top of the estimated worst case magnitude, so that the rounding errors of the
+// bootstraps don't push a value right at the limit out of the interval.
+const SAFETY_MARGIN: f64 = 1.25;
+
+/// The problems that can be found while planning the encoding.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlanError {
+    // The bits of precision needed and the most the bootstrap can hold
+    NotEnoughPrecision(usize, usize),
+}
+
+impl fmt::Display for PlanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlanError::NotEnoughPrecision(needed, max) => write!(
+                f,
+                "the encoding needs {} bits of precision but at most {} fit in the bootstrap, \
+                 try scaling the features",
+                needed, max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PlanError {}
+
+// The bits of precision telling apart values of size `resolution` in [-limit, limit], at least the
+// ones of the parameters.
+fn required_nb_bit_precision(limit: f64, resolution: f64) -> usize {
+    if resolution < f64::MAX && resolution > 0. {
+        ((2. * limit / resolution).log2().ceil() as usize).max(PARAMS.nb_bit_precision)
+    } else {
+        PARAMS.nb_bit_precision
+    }
+}
+
+// The bits of the message have to fit in the polynomial used by the bootstrap, along with the
+// padding and the bit of the sign.
+pub(crate) fn max_nb_bit_precision(nb_bit_padding: usize) -> usize {
+    ((PARAMS.rlwe_setting.polynomial_size as f64).log2() as usize)
+        .saturating_sub(nb_bit_padding + 1)
+}
+
+/// The encoding parameters used to encrypt every value of a computation.
+///
+/// All the ciphertexts combined by `Cipherfloat` operators have to share the same encoder, so a
+/// single interval is chosen to hold the inputs and every intermediate value of the computation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncodingPlan {
+    pub min: f64,
+    pub max: f64,
+    pub nb_bit_precision: usize,
+    pub nb_bit_padding: usize,
+    // The worst case magnitude estimated for the intermediate values
+    pub magnitude: f64,
+}
+
+impl EncodingPlan {
+    /// Plans the encoding of the classification of the rows of `x` with the model `beta`, the rows
+    /// are only read once so they can be streamed from the data file.
+    pub fn for_classification<I>(x: I, beta: &Vec<f64>) -> EncodingPlan
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[f64]>,
+    {
+        // The sigmoid bootstrap outputs a class in {-1, 1}
+        let mut magnitude: f64 = 1.;
+        let mut resolution: f64 = f64::MAX;
+        for b in beta {
+            magnitude = magnitude.max(b.abs());
+        }
+        for row in x {
+            let row = row.as_ref();
+            // The partial sums of x.beta are all bounded by the sum of the absolute products
+            let mut xbeta: f64 = 0.;
+            for j in 0..row.len().min(beta.len()) {
+                let product = (row[j] * beta[j]).abs();
+                magnitude = magnitude.max(row[j].abs());
+                if product > 0. {
+                    resolution = resolution.min(product);
+                }
+                xbeta += product;
+            }
+            magnitude = magnitude.max(xbeta);
+        }
+        EncodingPlan::new(magnitude, resolution)
+    }
+
+    /// Plans the encoding of `nb_iterations` iterations of the training on `x` labelled by `y`,
+    /// using the diagonal inverse hessian `h_tild_inv` and the initial model `beta`.
+    ///
+    /// The iterations are replayed in clear to bound the intermediate values along the actual
+    /// trajectory of the model, and the interval has to tell apart the coefficients of the trained
+    /// model. Fails if that needs more bits of precision than the bootstrap can hold.
+    pub fn for_training(
+        x: &Vec<Vec<f64>>,
+        y: &Vec<f64>,
+        h_tild_inv: &Vec<Vec<f64>>,
+        beta: &Vec<f64>,
+        nb_iterations: usize,
+    ) -> Result<EncodingPlan, PlanError> {
+        let n = x.len();
+        let d = beta.len();
+        // The labels are encoded as -1 and 1
+        let mut magnitude: f64 = 1.;
+        for i in 0..n {
+            for j in 0..d {
+                magnitude = magnitude.max(x[i][j].abs());
+            }
+        }
+
+        // Follows the iterations of the trainer:
+        // a_i = (0.5 - 0.25 * y_i * x_i.beta) * y_i, g = sum(x_i * a_i), beta = beta - h_inv * g
+        // and bounds the partial sums of the dot products by the sums of the absolute products.
+        let mut beta = beta.clone();
+        for b in &beta {
+            magnitude = magnitude.max(b.abs());
+        }
+        for _ in 0..nb_iterations {
+            let mut a: Vec<f64> = vec![0.; n];
+            for i in 0..n {
+                let mut xbeta: f64 = 0.;
+                let mut sum_abs: f64 = 0.;
+                for j in 0..d {
+                    xbeta += x[i][j] * beta[j];
+                    sum_abs += (x[i][j] * beta[j]).abs();
+                }
+                a[i] = (0.5 - 0.25 * y[i] * xbeta) * y[i];
+                magnitude = magnitude.max(sum_abs).max(a[i].abs());
+            }
+            for j in 0..d {
+                let mut g: f64 = 0.;
+                let mut sum_abs: f64 = 0.;
+                for i in 0..n {
+                    g += x[i][j] * a[i];
+                    sum_abs += (x[i][j] * a[i]).abs();
+                }
+                let delta = h_tild_inv[j][j] * g;
+                beta[j] -= delta;
+                magnitude = magnitude.max(sum_abs).max(delta.abs()).max(beta[j].abs());
+            }
+        }
+
+        // The coefficients of the trained model have to stay apart from 0
+        let mut resolution: f64 = f64::MAX;
+        for b in &beta {
+            if *b != 0. {
+                resolution = resolution.min(b.abs());
+            }
+        }
+        let plan = EncodingPlan::new(magnitude, resolution);
+        let nb_bit_precision = required_nb_bit_precision(plan.max, resolution);
+        if nb_bit_precision > plan.nb_bit_precision {
+            return Err(PlanError::NotEnoughPrecision(
+                nb_bit_precision,
+                plan.nb_bit_precision,
+            ));
+        }
+        Ok(plan)
+    }
+
+    // Builds the plan of an interval holding `magnitude`, trying to tell apart values of size
+    // `resolution`.
+    fn new(magnitude: f64, resolution: f64) -> EncodingPlan {
+        let limit = magnitude * SAFETY_MARGIN;
+        // Every operator bootstraps its result, which gives back the padding it consumed, so
+        // one bit per operator on top of the one needed by the bootstrap is enough.
+        let nb_bit_padding = PARAMS.nb_bit_padding.max(2);
+        let nb_bit_precision = required_nb_bit_precision(limit, resolution)
+            .min(max_nb_bit_precision(nb_bit_padding));
+        EncodingPlan {
+            min: -limit,
+            max: limit,
+            nb_bit_precision,
+            nb_bit_padding,
+            magnitude,
+        }
+    }
+
+    /// The smallest difference between two values that the encoding can represent.
+    pub fn precision(&self) -> f64 {
+        (self.max - self.min) / (1u64 << self.nb_bit_precision) as f64
+    }
+
+    /// Builds the encoder described by this plan.
+    pub fn encoder(&self) -> crypto_api::Encoder {
+        crypto_api::Encoder::new(
+            self.min,
+            self.max,
+            self.nb_bit_precision,
+            self.nb_bit_padding,
+        )
+        .unwrap()
+    }
+
+    /// Prints the chosen parameters.
+    pub fn report(&self) {
+        println!("Encoding plan:");
+        println!("  worst case magnitude: {}", self.magnitude);
+        println!("  interval: [{}, {}]", self.min, self.max);
+        println!("  precision bits: {}", self.nb_bit_precision);
+        println!("  padding bits: {}", self.nb_bit_padding);
+        println!("  expected precision: {}", self.precision());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiny_dataset() -> (Vec<Vec<f64>>, Vec<f64>, Vec<Vec<f64>>) {
+        let x = vec![
+            vec![1., 0.2],
+            vec![0.8, 0.5],
+            vec![-1., 0.4],
+            vec![-0.6, 0.1],
+        ];
+        let y = vec![1., 1., -1., -1.];
+        let h_tild_inv = vec![vec![-1.27, 0.], vec![0., -6.67]];
+        (x, y, h_tild_inv)
+    }
+
+    #[test]
+    fn classification_plan_holds_the_scores() {
+        let x = vec![vec![1., 2.], vec![3., -1.]];
+        let beta = vec![0.5, 1.];
+        let plan = EncodingPlan::for_classification(&x, &beta);
+        // |x_1| = 3 is larger than the sums of the absolute products, 2.5
+        assert_eq!(plan.magnitude, 3.);
+        assert!(plan.min <= -3. && plan.max >= 3.);
+    }
+
+    #[test]
+    fn training_plan_follows_the_iterations() {
+        let (x, y, h_tild_inv) = tiny_dataset();
+        let beta = vec![0.001; 2];
+        let plan = EncodingPlan::for_training(&x, &y, &h_tild_inv, &beta, 9).unwrap();
+        // The model converges to about (2.26, 0.18), the worst case bound used to compound over
+        // the iterations instead
+        assert!(plan.magnitude > 2.26 && plan.magnitude < 3., "{}", plan.magnitude);
+        assert!(plan.precision() < 0.18, "{}", plan.precision());
+    }
+
+    #[test]
+    fn training_plan_fails_without_enough_precision() {
+        let x = vec![vec![1., 1.]];
+        let y = vec![1.];
+        let h_tild_inv = vec![vec![1., 0.], vec![0., 1.]];
+        // 0.001 can't be told apart from 0 in [-1250, 1250] with 9 bits
+        let beta = vec![1000., 0.001];
+        let max = max_nb_bit_precision(PARAMS.nb_bit_padding.max(2));
+        assert_eq!(
+            EncodingPlan::for_training(&x, &y, &h_tild_inv, &beta, 0),
+            Err(PlanError::NotEnoughPrecision(22, max))
+        );
+    }
+
+    #[test]
+    fn precision_fits_in_the_polynomial() {
+        assert_eq!(max_nb_bit_precision(2), 9);
+        assert_eq!(max_nb_bit_precision(64), 0);
+        assert_eq!(required_nb_bit_precision(1., f64::MAX), PARAMS.nb_bit_precision);
+        assert_eq!(required_nb_bit_precision(1., 1. / 512.), 10);
+    }
+}
@@ -0,0 +1,104 @@
+//! A module recording the keys and the ciphertexts of an encrypted classification, so that a run
+//! can be reproduced bit for bit on another machine.
+//!
+//! The randomness of the key generation and of the encryption is drawn by concrete, which offers
+//! no way to seed it. The evaluation of the ciphertexts is deterministic though, so replaying the
+//! recorded keys and ciphertexts reproduces the run exactly. A recording holds the secret key: it
+//! is insecure and only meant for debugging.
+use crate::reg;
+use crate::zqz;
+
+use reg::error::Error;
+use reg::utils::hash_file;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use zqz::keys::EncryptKey;
+use zqz::matrix::CipherMatrix;
+use zqz::vector::CipherVector;
+
+// The file of the recording holding the ciphertexts.
+const CIPHERTEXTS_FILE: &str = "ciphertexts.bin";
+
+/// What to do with the keys and the ciphertexts of a run.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Replay {
+    // Save them to the directory
+    Record(String),
+    // Use the ones saved to the directory instead of generating and encrypting new ones
+    Replay(String),
+}
+
+// The ciphertexts of a recorded classification.
+#[derive(Serialize, Deserialize)]
+struct Recording {
+    // The FNV-1a hashes of the data file and of the model file
+    dataset_hash: String,
+    model_hash: String,
+    e_x: Vec<u8>,
+    e_beta: Vec<u8>,
+}
+
+/// The prefix of the keys of the recording in `directory`.
+pub fn keys_prefix(directory: &str) -> String {
+    Path::new(directory).join("keys").to_string_lossy().to_string()
+}
+
+/// Records the keys and the encrypted inputs of the classification of the data file with the model.
+pub fn record(
+    directory: &str,
+    data_file: &str,
+    model_file: &str,
+    sk: &EncryptKey,
+    e_x: &CipherMatrix,
+    e_beta: &CipherVector,
+) -> Result<(), Error> {
+    let io_error = |e: std::io::Error| Error::Io(directory.to_string(), e.to_string());
+    std::fs::create_dir_all(directory).map_err(io_error)?;
+    sk.save_to_files(&keys_prefix(directory));
+    let recording = Recording {
+        dataset_hash: hash_file(data_file).unwrap_or_default(),
+        model_hash: hash_file(model_file).unwrap_or_default(),
+        e_x: e_x.to_bytes(),
+        e_beta: e_beta.to_bytes(),
+    };
+    std::fs::write(
+        Path::new(directory).join(CIPHERTEXTS_FILE),
+        bincode::serialize(&recording).unwrap(),
+    )
+    .map_err(io_error)?;
+    println!("Recorded the keys and the ciphertexts to {} (contains the secret key)", directory);
+    Ok(())
+}
+
+/// Loads the keys and the encrypted inputs recorded in `directory`, warning if they were recorded
+/// with another data file or model.
+pub fn replay(
+    directory: &str,
+    data_file: &str,
+    model_file: &str,
+) -> Result<(EncryptKey, CipherMatrix, CipherVector), Error> {
+    let ciphertexts_file = Path::new(directory).join(CIPHERTEXTS_FILE);
+    let corrupted = || {
+        Error::Io(
+            ciphertexts_file.to_string_lossy().to_string(),
+            "not a recording of a classification".to_string(),
+        )
+    };
+    if !EncryptKey::keys_exist(&keys_prefix(directory)) {
+        return Err(Error::Io(directory.to_string(), "no recorded keys".to_string()));
+    }
+    let data = std::fs::read(&ciphertexts_file)
+        .map_err(|e| Error::Io(directory.to_string(), e.to_string()))?;
+    let recording: Recording = bincode::deserialize(&data).map_err(|_| corrupted())?;
+    if recording.dataset_hash != hash_file(data_file).unwrap_or_default() {
+        println!("Warning: the recording was made with another data file");
+    }
+    if recording.model_hash != hash_file(model_file).unwrap_or_default() {
+        println!("Warning: the recording was made with another model");
+    }
+    let sk = EncryptKey::load_from_files(&keys_prefix(directory));
+    let e_x = CipherMatrix::from_bytes(&recording.e_x, &sk).ok_or_else(corrupted)?;
+    let e_beta = CipherVector::from_bytes(&recording.e_beta, &sk).ok_or_else(corrupted)?;
+    println!("Replaying the keys and the ciphertexts recorded in {}", directory);
+    Ok((sk, e_x, e_beta))
+}
@@ -0,0 +1,174 @@
+//! A module scaling the characteristics of the data to comparable ranges.
+use clap::ArgEnum;
+use serde::{Deserialize, Serialize};
+
+/// The preprocessing applied to every characteristic.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum, Debug, Serialize, Deserialize)]
+pub enum ScalingKind {
+    /// keep the values as they are
+    None,
+    /// center on the mean and divide by the standard deviation
+    ZScore,
+    /// map the smallest value to 0 and the greatest to 1
+    MinMax,
+}
+
+/// The scaling computed on the training data, x' = (x - offset) / scale for every characteristic.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scaler {
+    pub kind: ScalingKind,
+    pub offsets: Vec<f64>,
+    pub scales: Vec<f64>,
+}
+
+impl Default for Scaler {
+    fn default() -> Scaler {
+        Scaler {
+            kind: ScalingKind::None,
+            offsets: Vec::new(),
+            scales: Vec::new(),
+        }
+    }
+}
+
+impl Scaler {
+    /// Computes the scaling of the characteristics of `x`.
+    pub fn fit(x: &Vec<Vec<f64>>, kind: ScalingKind) -> Scaler {
+        if kind == ScalingKind::None || x.is_empty() {
+            return Scaler::default();
+        }
+        let n = x.len();
+        let d = x[0].len();
+        let mut offsets: Vec<f64> = vec![0.; d];
+        let mut scales: Vec<f64> = vec![1.; d];
+        for j in 0..d {
+            let (offset, scale) = match kind {
+                ScalingKind::ZScore => {
+                    let mean = x.iter().map(|row| row[j]).sum::<f64>() / n as f64;
+                    let variance =
+                        x.iter().map(|row| (row[j] - mean).powi(2)).sum::<f64>() / n as f64;
+                    (mean, variance.sqrt())
+                }
+                ScalingKind::MinMax => {
+                    let min = x.iter().map(|row| row[j]).fold(f64::MAX, f64::min);
+                    let max = x.iter().map(|row| row[j]).fold(f64::MIN, f64::max);
+                    (min, max - min)
+                }
+                ScalingKind::None => (0., 1.),
+            };
+            offsets[j] = offset;
+            // A constant characteristic is only shifted
+            scales[j] = if scale > 0. { scale } else { 1. };
+        }
+        Scaler {
+            kind,
+            offsets,
+            scales,
+        }
+    }
+
+    /// Scales the characteristics of `x`.
+    pub fn transform(&self, x: &mut Vec<Vec<f64>>) {
+        if self.kind == ScalingKind::None {
+            return;
+        }
+        for row in x.iter_mut() {
+            for j in 0..row.len().min(self.scales.len()) {
+                row[j] = (row[j] - self.offsets[j]) / self.scales[j];
+            }
+        }
+    }
+
+    /// Folds the scaling into the model, so that x'.beta = x.beta_folded + constant and the
+    /// unscaled data can be used as it is.
+    pub fn fold(&self, beta: &Vec<f64>) -> (Vec<f64>, f64) {
+        if self.kind == ScalingKind::None {
+            return (beta.clone(), 0.);
+        }
+        let mut folded: Vec<f64> = Vec::new();
+        let mut constant: f64 = 0.;
+        for j in 0..beta.len() {
+            let b = beta[j] / self.scales[j];
+            constant -= self.offsets[j] * b;
+            folded.push(b);
+        }
+        (folded, constant)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dataset() -> Vec<Vec<f64>> {
+        vec![vec![1., 4., 2.], vec![3., 4., -1.], vec![-2., 4., 0.5]]
+    }
+
+    fn dot(x: &Vec<f64>, beta: &Vec<f64>) -> f64 {
+        x.iter().zip(beta).map(|(a, b)| a * b).sum()
+    }
+
+    // Checks that the scaled data with beta gives the scores of the data with the folded beta.
+    fn assert_fold_matches_transform(scaler: &Scaler, x: &Vec<Vec<f64>>) {
+        let beta = vec![0.5, -2., 1.5];
+        let (folded, constant) = scaler.fold(&beta);
+        let mut scaled = x.clone();
+        scaler.transform(&mut scaled);
+        for i in 0..x.len() {
+            let expected = dot(&scaled[i], &beta);
+            assert!((dot(&x[i], &folded) + constant - expected).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn z_score_fold_matches_transform() {
+        let x = dataset();
+        let scaler = Scaler::fit(&x, ScalingKind::ZScore);
+        let mut scaled = x.clone();
+        scaler.transform(&mut scaled);
+        // The first characteristic is centered and of unit variance
+        let mean = scaled.iter().map(|row| row[0]).sum::<f64>() / 3.;
+        let variance = scaled.iter().map(|row| row[0] * row[0]).sum::<f64>() / 3.;
+        assert!(mean.abs() < 1e-9 && (variance - 1.).abs() < 1e-9);
+        assert_fold_matches_transform(&scaler, &x);
+    }
+
+    #[test]
+    fn min_max_fold_matches_transform() {
+        let x = dataset();
+        let scaler = Scaler::fit(&x, ScalingKind::MinMax);
+        let mut scaled = x.clone();
+        scaler.transform(&mut scaled);
+        assert_eq!(
+            scaled.iter().map(|row| row[2]).collect::<Vec<f64>>(),
+            vec![1., 0., 0.5]
+        );
+        assert_fold_matches_transform(&scaler, &x);
+    }
+
+    #[test]
+    fn constant_characteristics_are_only_shifted() {
+        let x = dataset();
+        for kind in [ScalingKind::ZScore, ScalingKind::MinMax] {
+            let scaler = Scaler::fit(&x, kind);
+            assert_eq!((scaler.offsets[1], scaler.scales[1]), (4., 1.));
+            let mut scaled = x.clone();
+            scaler.transform(&mut scaled);
+            assert!(scaled.iter().all(|row| row[1] == 0.));
+            assert_fold_matches_transform(&scaler, &x);
+        }
+    }
+
+    #[test]
+    fn default_scaler_is_the_identity() {
+        let x = dataset();
+        let scaler = Scaler::default();
+        assert_eq!(Scaler::fit(&x, ScalingKind::None), scaler);
+        let mut scaled = x.clone();
+        scaler.transform(&mut scaled);
+        assert_eq!(scaled, x);
+        let beta = vec![0.5, -2., 1.5];
+        assert_eq!(scaler.fold(&beta), (beta.clone(), 0.));
+        assert_fold_matches_transform(&scaler, &x);
+    }
+}
@@ -5,46 +5,67 @@ use crate::zqz;
 use clear::matrix::PlainMatrix;
 use clear::vector::PlainVector;
 
-use reg::utils::{parse_data_file, save_encoded_vector, save_vector};
+use reg::checkpoint::{settings_hash, Checkpoint};
+use reg::dataset::{load_dataset, DatasetOptions};
+use reg::planner::EncodingPlan;
+use reg::error::Error;
+use reg::labels::LabelMapping;
+use reg::model::{Model, TrainingInfo};
+use reg::scaling::Scaler;
+use reg::utils::{hash_file, save_vector};
 
 use crate::measure_duration;
 use crate::PARAMS;
 
+use zqz::cipherfloat::Cipherfloat;
+use zqz::cost::OperationCount;
 use zqz::keys::EncryptKey;
 use zqz::matrix::CipherMatrix;
+use zqz::polynomial::Polynomial;
 use zqz::vector::CipherVector;
 
-pub fn train_data_plain(data_file: &str, model_file: &str) -> Vec<f64> {
-    let (x, y) = parse_data_file(&data_file);
-    //TODO check if len X == 0 , than throw back error
-    let n = x.len();
-    let d = x[0].len();
-
-    let beta: Vec<f64> = vec![0.001; d];
-    //A BAD APPROXIMATION IN THE HESSIAN INVERSE MATRIX
-    //WILL CAUSE US TO HAVE A SLOW PROGRESS TO THE OPTIMUM SOLUTION FOR BETA
-    //THIS IS WHY WE CAN TOLERATE TO DO MANY ITERATIONS IN CALCULATING THE INVERSE MATRIX
-    //WE WILL GAIN IN CALCULATION OF THE BETA
-    let nbr_iters = 20;
-
-    let mut h_tild: Vec<Vec<f64>> = vec![vec![0.0; d]; d];
-    let mut h_tild_inv: Vec<Vec<f64>> = vec![vec![0.0; d]; d];
-    let mut sum: Vec<f64> = vec![0.0; n];
+//A BAD APPROIMATION IN THE HESSIAN INVERSE MATRIX
+//WILL CAUSE US TO HAVE A SLOW PROGRESS TO THE OPTIMUM SOLUTION FOR BETA
+//THIS IS WHY WE CAN TOLERATE TO DO MANY ITERATIONS IN CALCULATING THE INVERSE MATRIX
+//WE WILL GAIN IN CALCULATION OF THE BETA
+const NBR_ITERS_PLAIN: usize = 20;
+const NBR_ITERS_FHE: usize = 10;
+// The value of every coefficient of beta before the first iteration
+const INITIAL_BETA: f64 = 0.001;
+// Newton's method with a fixed diagonal approximation of the hessian
+const ALGORITHM: &str = "fixed-hessian-newton";
 
-    //Calculation of the hessian matrix
-    for i in 0..n {
-        for j in 0..d {
-            sum[i] = sum[i] + x[i][j];
-        }
+// Describes the training of a model on the data file, the iterations counted from 1 to `nbr_iters`
+// excluded, the first one being the initial model.
+fn training_info(data_file: &str, nbr_iters: usize, fhe_prefix: Option<String>) -> TrainingInfo {
+    TrainingInfo {
+        algorithm: ALGORITHM.to_string(),
+        // The number of iterations actually run
+        nbr_iters: nbr_iters - 1,
+        initial_beta: INITIAL_BETA,
+        dataset_hash: hash_file(data_file).unwrap_or_default(),
+        fhe_prefix,
     }
-    for j in 0..d {
-        let mut temp = 0.0;
-        for i in 1..n {
-            temp = temp + x[i][j] * sum[i];
-        }
-        h_tild[j][j] = -temp / 4.0;
-        h_tild_inv[j][j] = invers_number_using_newton_raphson(h_tild[j][j]);
+}
+
+pub fn train_data_plain(
+    data_file: &str,
+    model_file: &str,
+    options: &DatasetOptions,
+) -> Result<Vec<f64>, Error> {
+    let (mut x, labels) = load_dataset(&data_file, options)?;
+    if x.is_empty() {
+        return Err(Error::EmptyDataset(data_file.to_string()));
     }
+    let mapping = LabelMapping::from_options(&labels, options)?;
+    let y = mapping.encode(&labels)?;
+    let scaler = Scaler::fit(&x, options.scaling);
+    scaler.transform(&mut x);
+    let n = x.len();
+    let d = x[0].len();
+    let beta: Vec<f64> = vec![INITIAL_BETA; d];
+    let nbr_iters = NBR_ITERS_PLAIN;
+    let h_tild_inv = hessian_inverse(&x);
 
     //Encoding 1D vectors and 2D vectors into Vector object and Matrix for easy calculations
     let p_x = PlainMatrix::new(x);
@@ -52,54 +73,46 @@ pub fn train_data_plain(data_file: &str, model_file: &str) -> Vec<f64> {
     let p_h_tild_inv = PlainMatrix::new(h_tild_inv);
     let mut p_beta = PlainVector::new(beta);
 
-    let mut p_deltas_history: Vec<PlainVector> = Vec::new();
     for _ in 1..nbr_iters {
-        let g: Vec<f64> = vec![0.0; d];
-        let mut p_g = PlainVector::new(g);
-        for i in 1..n {
-            let a = (0.5 - 0.25 * p_y.get(i) * (&p_x.get_row(i) * &p_beta)) * p_y.get(i);
-            p_g = &p_g + &(&p_x.get_row(i) * a);
-        }
-        let p_delta = &p_h_tild_inv * &p_g;
-        p_beta = &p_beta + &(&p_delta * -1.0);
-        p_deltas_history.push(p_delta);
+        p_beta = iteration_plain(&p_x, &p_y, &p_h_tild_inv, &p_beta, n);
     }
 
-    save_encoded_vector(&p_beta.plainvector, model_file);
+    let mut model = Model::new(p_beta.plainvector.clone(), mapping, scaler);
+    model.training = Some(training_info(data_file, nbr_iters, None));
+    model.save(model_file)?;
     save_vector(&p_beta.plainvector,&format!("{}.debug.txt", model_file));
-    p_beta.plainvector
+    Ok(p_beta.plainvector)
 }
 
-pub fn train_data_fhe(data_file: &str, model_file: &str) -> Vec<f64> {
-    let (x, y) = parse_data_file(&data_file);
+/// Trains the model on the encrypted data, the encrypted beta is checkpointed after every iteration
+/// and with `resume` the training continues from the last checkpoint.
+pub fn train_data_fhe(
+    data_file: &str,
+    model_file: &str,
+    options: &DatasetOptions,
+    resume: bool,
+) -> Result<Vec<f64>, Error> {
+    let (mut x, labels) = load_dataset(&data_file, options)?;
+    if x.is_empty() {
+        return Err(Error::EmptyDataset(data_file.to_string()));
+    }
+    let mapping = LabelMapping::from_options(&labels, options)?;
+    let y = mapping.encode(&labels)?;
+    let scaler = Scaler::fit(&x, options.scaling);
+    scaler.transform(&mut x);
     let n = x.len();
-    let d = y.len();
-    //A BAD APPROIMATION IN THE HESSIAN INVERSE MATRIX
-    //WILL CAUSE US TO HAVE A SLOW PROGRESS TO THE OPTIMUM SOLUTION FOR BETA
-    //THIS IS WHY WE CAN TOLERATE TO DO MANY ITERATIONS IN CALCULATING THE INVERSE MATRIX
-    //WE WILL GAIN IN CALCULATION OF THE BETA
-    let nbr_iters = 10;
+    let d = x[0].len();
+    let nbr_iters = NBR_ITERS_FHE;
 
-    let mut h_tild: Vec<Vec<f64>> = vec![vec![0.0; d]; d];
-    let mut h_tild_inv: Vec<Vec<f64>> = vec![vec![0.0; d]; d];
-    let beta: Vec<f64> = vec![0.001; d];
-    let g: Vec<f64> = vec![0.0; d];
-    let mut sum: Vec<f64> = vec![0.0; n];
-    //Calculating the Hessian matrix
-    for i in 0..n {
-        for j in 0..d {
-            sum[i] = sum[i] + x[i][j];
-        }
-    }
+    let beta: Vec<f64> = vec![INITIAL_BETA; d];
+    let h_tild_inv = hessian_inverse(&x);
 
-    for j in 0..d {
-        let mut temp = 0.0;
-        for i in 1..n {
-            temp = temp + x[i][j] * sum[i];
-        }
-        h_tild[j][j] = -temp / 4.0;
-        h_tild_inv[j][j] = invers_number_using_newton_raphson(h_tild[j][j]);
-    }
+    // The first iteration is the initial model
+    let plan = EncodingPlan::for_training(&x, &y, &h_tild_inv, &beta, nbr_iters - 1)?;
+    plan.report();
+    let encoder = plan.encoder();
+    // The inverse of the hessian is public, it isn't encrypted
+    let p_h_tild_inv = PlainMatrix::new(h_tild_inv);
 
     measure_duration!("1. Key Loading...",[
         let sk = if !EncryptKey::keys_exist(&PARAMS.gen_prefix()) {
@@ -111,32 +124,161 @@ pub fn train_data_fhe(data_file: &str, model_file: &str) -> Vec<f64> {
         };
     ]);
     measure_duration!("2. Encryption... ",[
-        let e_h_tild_inv : CipherMatrix = sk.encrypt_matrix(&h_tild_inv,-60.,60.);
-        let e_x : CipherMatrix          = sk.encrypt_matrix(&x,-60.,60.);
-        let e_y : CipherVector          = sk.encrypt_vector(&y,-60.,60.);
-        let e_g : CipherVector          = sk.encrypt_vector(&g,-60.,60.);
-        let mut e_beta : CipherVector   = sk.encrypt_vector(&beta,-60.,60.);
+        let e_x : CipherMatrix          = sk.encrypt_matrix_with_encoder(&x, &encoder);
+        let e_x_t : CipherMatrix        = e_x.transpose();
+        let e_y : CipherVector          = sk.encrypt_vector_with_encoder(&y, &encoder);
+        let mut e_beta : CipherVector   = sk.encrypt_vector_with_encoder(&beta, &encoder);
     ]);
 
+    let checkpoint_file = format!("{}.checkpoint", model_file);
+    let dataset_hash = hash_file(data_file).unwrap_or_default();
+    let settings_hash = settings_hash(&mapping, &scaler, &plan);
+    let mut first_iteration: usize = 1;
+    if resume {
+        match Checkpoint::load(&checkpoint_file)? {
+            Some(checkpoint) => {
+                e_beta = checkpoint.restore(
+                    &checkpoint_file,
+                    &sk,
+                    &PARAMS.gen_prefix(),
+                    &dataset_hash,
+                    &settings_hash,
+                )?;
+                first_iteration = checkpoint.next_iteration;
+                println!("Resuming from iteration {}", first_iteration);
+            }
+            None => println!("No checkpoint found, starting from the first iteration"),
+        }
+    }
+
     //let mut e_deltas_history: Vec<CipherVector> = Vec::new();
-    for _ in 1..nbr_iters {
-        let mut g_tmp = e_g.clone();
-        for i in 1..n {
-            let e_a: zqz::cipherfloat::Cipherfloat =
-                &(&(&(&e_y.get(i) * &(&e_x.get_row(i) * &e_beta)) * (-0.25 as f64)) + 0.5)
-                    * &e_y.get(i);
-            g_tmp = &g_tmp + &(&e_x.get_row(i) * &e_a);
+    measure_duration!(
+        "3. Training... ",
+        [for iteration in first_iteration..nbr_iters {
+            e_beta = iteration_fhe(&e_x, &e_x_t, &e_y, &p_h_tild_inv, &e_beta, n);
+            Checkpoint::new(
+                iteration + 1,
+                &PARAMS.gen_prefix(),
+                &dataset_hash,
+                &settings_hash,
+                &e_beta,
+            )
+            .save(&checkpoint_file)?;
+        }]
+    );
+
+    measure_duration!("4. Decryption... ",[
+        let d_beta = sk.decrypt_vector(&e_beta);
+    ]);
+
+    let mut model = Model::new(d_beta.clone(), mapping, scaler);
+    model.training = Some(training_info(data_file, nbr_iters, Some(PARAMS.gen_prefix())));
+    model.save(model_file)?;
+    // The model is saved, the checkpoint isn't needed anymore
+    let _ = std::fs::remove_file(&checkpoint_file);
+
+    Ok(d_beta)
+}
+
+// Computes the inverse of the diagonal approximation of the hessian matrix of the records of `x`.
+fn hessian_inverse(x: &Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+    let n = x.len();
+    let d = x[0].len();
+    let mut h_tild: Vec<Vec<f64>> = vec![vec![0.0; d]; d];
+    let mut h_tild_inv: Vec<Vec<f64>> = vec![vec![0.0; d]; d];
+    let mut sum: Vec<f64> = vec![0.0; n];
+
+    //Calculation of the hessian matrix
+    for i in 0..n {
+        for j in 0..d {
+            sum[i] = sum[i] + x[i][j];
         }
-        let e_delta = &e_h_tild_inv * &g_tmp;
-        e_beta = &e_beta + &(&e_delta * (-1.0 as f64));
-        //e_deltas_history.push(e_delta);
     }
+    for j in 0..d {
+        let mut temp = 0.0;
+        for i in 0..n {
+            temp = temp + x[i][j] * sum[i];
+        }
+        h_tild[j][j] = -temp / 4.0;
+        h_tild_inv[j][j] = invers_number_using_newton_raphson(h_tild[j][j]);
+    }
+    h_tild_inv
+}
+
+// The degree 1 approximation 1/2 - z/4 of sigmoid(-z), z = y * x.beta, weighting the records in
+// the gradient.
+fn sigmoid_approximation() -> Polynomial {
+    Polynomial::new(vec![0.5, -0.25])
+}
 
-    let d_beta = sk.decrypt_vector(&e_beta);
+// Computes the next beta from the `n` records of `p_x` labelled by `p_y`.
+fn iteration_plain(
+    p_x: &PlainMatrix,
+    p_y: &PlainVector,
+    p_h_tild_inv: &PlainMatrix,
+    p_beta: &PlainVector,
+    n: usize,
+) -> PlainVector {
+    let sigmoid = sigmoid_approximation();
+    let a: Vec<f64> = (0..n)
+        .map(|i| sigmoid.eval(p_y.get(i) * (&p_x.get_row(i) * p_beta)) * p_y.get(i))
+        .collect();
+    // g = X^T . a
+    let p_g = &p_x.transpose() * &PlainVector::new(a);
+    let p_delta = p_h_tild_inv * &p_g;
+    p_beta + &(&p_delta * -1.0)
+}
 
-    save_encoded_vector(&d_beta, model_file);
+// Computes the next encrypted beta from the `n` encrypted records of `e_x` labelled by `e_y`, the
+// inverse of the hessian is public and stays in clear. `e_x_t` is the transpose of `e_x`, computed
+// once for all the iterations.
+fn iteration_fhe(
+    e_x: &CipherMatrix,
+    e_x_t: &CipherMatrix,
+    e_y: &CipherVector,
+    p_h_tild_inv: &PlainMatrix,
+    e_beta: &CipherVector,
+    n: usize,
+) -> CipherVector {
+    let sigmoid = sigmoid_approximation();
+    let mut e_a: Vec<Cipherfloat> = Vec::new();
+    for i in 0..n {
+        let e_z = &e_y.get(i) * &(&e_x.get_row(i) * e_beta);
+        e_a.push(&sigmoid.eval_cipherfloat(&e_z) * &e_y.get(i));
+    }
+    let e_a = CipherVector {
+        ciphervector: e_a,
+        dim: n,
+        evaluation_key: e_beta.evaluation_key.clone(),
+    };
+    // g = X^T . a
+    let e_g = e_x_t * &e_a;
+    let e_delta = p_h_tild_inv * &e_g;
+    e_beta + &(-&e_delta)
+}
 
-    d_beta
+/// Counts the cipherfloat operations performed by `train_data_fhe` on `n` records of dimension `d`.
+pub fn count_operations_fhe(n: usize, d: usize) -> OperationCount {
+    // a = (y * (x.beta) * -0.25 + 0.5) * y
+    let a = OperationCount::dot_product(d)
+        + OperationCount {
+            multiplications: 2,
+            mul_constants: 1,
+            add_constants: 1,
+            ..Default::default()
+        };
+    // g = X^T . a, a dot product of the n records for every characteristic
+    let gradient = OperationCount::dot_product(n) * d as u64;
+    // beta = beta + -(h_tild_inv * g), the inverse of the hessian is diagonal and in clear, its
+    // zeros are skipped and the negation doesn't bootstrap
+    let update = OperationCount {
+        mul_constants: d as u64,
+        negations: d as u64,
+        additions: d as u64,
+        ..Default::default()
+    };
+    let iteration = a * n as u64 + gradient + update;
+    iteration * (NBR_ITERS_FHE - 1) as u64
 }
 
 fn invers_number_using_newton_raphson(a: f64) -> f64 {
@@ -155,3 +297,151 @@ fn invers_number_using_newton_raphson(a: f64) -> f64 {
     //println!("inv of : {} is {}", a, xk);
     return xk;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zqz::functions::{encoder_like, granularity};
+    use crate::zqz::testing::{encoder_of, temp_file};
+
+    #[test]
+    fn hessian_covers_every_record() {
+        let x = vec![vec![2., 0.], vec![0., 1.]];
+        let h_tild_inv = hessian_inverse(&x);
+        // h_00 = -(2 * 2) / 4 and h_11 = -(1 * 1) / 4, the first record is part of the sum
+        assert!((h_tild_inv[0][0] + 1.).abs() < 1e-6);
+        assert!((h_tild_inv[1][1] + 4.).abs() < 1e-6);
+        assert_eq!(h_tild_inv[0][1], 0.);
+    }
+
+    #[test]
+    fn training_info_records_the_iterations_run() {
+        let info = training_info("missing_training_file", NBR_ITERS_FHE, None);
+        // The loops run from 1 to NBR_ITERS_FHE excluded
+        assert_eq!(info.nbr_iters, (1..NBR_ITERS_FHE).count());
+    }
+
+    #[test]
+    fn training_rejects_empty_datasets() {
+        let data_file = temp_file("empty_training_data");
+        std::fs::write(&data_file, "").unwrap();
+        let model_file = temp_file("empty_training_model");
+        let options = DatasetOptions::default();
+        let empty = Err(Error::EmptyDataset(data_file.clone()));
+        assert_eq!(train_data_plain(&data_file, &model_file, &options), empty);
+        assert_eq!(train_data_fhe(&data_file, &model_file, &options, false), empty);
+    }
+
+    #[test]
+    fn fhe_training_matches_plain_training() {
+        // The last record contradicts the first one, so that the second step still moves beta
+        let x = vec![
+            vec![1., 1.],
+            vec![-1., 1.],
+            vec![-1., -1.],
+            vec![1., 1.],
+        ];
+        let y = vec![1., 1., -1., -1.];
+        let n = x.len();
+        let d = x[0].len();
+        // Two steps, beta goes from (0, 2/3) to (-2/9, 8/9)
+        let nbr_iters = 3;
+        let beta: Vec<f64> = vec![INITIAL_BETA; d];
+        let h_tild_inv = hessian_inverse(&x);
+
+        let p_x = PlainMatrix::new(x.clone());
+        let p_y = PlainVector::new(y.clone());
+        let p_h_tild_inv = PlainMatrix::new(h_tild_inv.clone());
+        let mut p_betas = vec![PlainVector::new(beta.clone())];
+        for k in 1..nbr_iters {
+            let p_beta = iteration_plain(&p_x, &p_y, &p_h_tild_inv, &p_betas[k - 1], n);
+            p_betas.push(p_beta);
+        }
+
+        // The greatest value computed by the iterations, the one following the last beta
+        // included, so that the encoder would still hold a further iteration
+        let mut max_value: f64 = 0.;
+        for p_beta in &p_betas {
+            let a: Vec<f64> = (0..n)
+                .map(|i| {
+                    let score = &p_x.get_row(i) * p_beta;
+                    max_value = max_value.max(score.abs());
+                    (0.5 - 0.25 * y[i] * score) * y[i]
+                })
+                .collect();
+            for j in 0..d {
+                let g: f64 = (0..n).map(|i| x[i][j] * a[i]).sum();
+                max_value = max_value.max(g.abs()).max((h_tild_inv[j][j] * g).abs());
+                max_value = max_value.max(p_beta.get(j).abs());
+            }
+            max_value = a.iter().fold(max_value, |max, a| max.max(a.abs()));
+        }
+        let bound = 1.25;
+        assert!(max_value < bound, "{}", max_value);
+        let encoder = encoder_of(-bound, bound);
+
+        let sk = EncryptKey::new_zero();
+        let e_x = sk.encrypt_matrix_with_encoder(&x, &encoder);
+        let e_x_t = e_x.transpose();
+        let e_y = sk.encrypt_vector_with_encoder(&y, &encoder);
+        let mut e_beta = sk.encrypt_vector_with_encoder(&beta, &encoder);
+        for _ in 1..nbr_iters {
+            e_beta = iteration_fhe(&e_x, &e_x_t, &e_y, &p_h_tild_inv, &e_beta, n);
+        }
+        let d_beta = sk.decrypt_vector(&e_beta);
+
+        // The roundings of an iteration: the encryption of the records rounds them by half a step,
+        // a product rounds its two squares and then its result, the other operations round their
+        // result by a step. They are followed along x.beta, the sigmoid, a and the gradient.
+        let step = granularity(&encoder);
+        let square_step = granularity(&encoder_like(&encoder, 0., (2. * bound).powi(2) / 4.));
+        let e_x = step / 2.;
+        let e_mul = square_step + step / 2.;
+        let max_beta = p_betas
+            .iter()
+            .flat_map(|p_beta| p_beta.plainvector.clone())
+            .fold(0., |max: f64, b| max.max(b.abs()));
+        let max_a = 0.5 + 0.25 * max_value;
+        let max_h_inv: f64 = (0..d).map(|j| h_tild_inv[j][j].abs()).fold(0., f64::max);
+        let e_score = d as f64 * (max_beta * e_x + e_mul) + (d - 1) as f64 * step;
+        let e_z = max_value * e_x + e_score + e_mul;
+        let e_sigmoid = 0.25 * e_z + step;
+        let e_a = max_a * e_x + e_sigmoid + e_mul;
+        let e_g = n as f64 * (max_a * e_x + e_a + e_mul) + (n - 1) as f64 * step;
+        // The product by the inverse hessian and the update of beta
+        let e_iteration = max_h_inv * e_g + 2. * step;
+        // The error of beta is carried to the next one by beta -> beta - H^-1 g(beta), which is
+        // linear of matrix I + H^-1 X^T X / 4
+        let carry: f64 = (0..d)
+            .map(|j| {
+                (0..d)
+                    .map(|k| {
+                        let xtx: f64 = (0..n).map(|i| x[i][j] * x[i][k]).sum();
+                        ((j == k) as u8 as f64 + h_tild_inv[j][j] * xtx / 4.).abs()
+                    })
+                    .sum::<f64>()
+            })
+            .fold(0., f64::max);
+        let mut tolerance = e_x;
+        for _ in 1..nbr_iters {
+            tolerance = carry * tolerance + e_iteration;
+        }
+        // A beta not carried from one iteration to the next would be off by the second step
+        let second_step = (0..d)
+            .map(|j| (p_betas[2].get(j) - p_betas[1].get(j)).abs())
+            .fold(0., f64::max);
+        assert!(tolerance < second_step, "{} {}", tolerance, second_step);
+
+        let p_beta = &p_betas[nbr_iters - 1];
+        assert_eq!(d_beta.len(), d);
+        for j in 0..d {
+            assert!(
+                (d_beta[j] - p_beta.get(j)).abs() < tolerance,
+                "beta[{}]: {} encrypted, {} in clear",
+                j,
+                d_beta[j],
+                p_beta.get(j)
+            );
+        }
+    }
+}
@@ -1,10 +1,14 @@
+use crate::reg;
+
+use reg::error::Error;
+use std::fmt;
 use std::fs::File;
 use std::io::{self, BufRead, Write};
 use std::path::Path;
 
 // The output is wrapped in a Result to allow matching on errors
 // Returns an Iterator to the Reader of the lines of the file.
-fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
+pub(super) fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
 where
     P: AsRef<Path>,
 {
@@ -12,91 +16,432 @@ where
     Ok(io::BufReader::new(file).lines())
 }
 
-pub fn save_encoded_vector(vector: &Vec<f64>, filename: &str) {
-    let encoded: Vec<u8> = bincode::serialize(&vector).unwrap();
-    std::fs::write(filename, &encoded);
-}
-
-pub fn load_encoded_vector(filename: &str) -> Vec<f64> {
-    let data = std::fs::read(filename).unwrap();
-    let decoded: Vec<f64> = bincode::deserialize(&data[..]).unwrap();
-    return decoded;
-}
-
 pub fn save_vector(vector: &Vec<f64>, filename: &str) {
     let mut f = File::create(filename).unwrap();
     for i in vector {
         writeln!(f, "{}", i);
     }
 }
-pub fn load_vector(filename: &str) -> Vec<f64> {
-    let mut result: Vec<f64> = Vec::new();
-    let lines: io::Lines<io::BufReader<File>> = read_lines(filename).unwrap();
-    for line in lines {
-        if let Ok(l) = line {
-            result.push(l.parse().unwrap());
+/// Computes the 64 bits FNV-1a hash of the content of a file.
+pub fn hash_file(filename: &str) -> io::Result<String> {
+    Ok(hash_bytes(&std::fs::read(filename)?))
+}
+
+/// Computes the 64 bits FNV-1a hash of the bytes.
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+pub fn save_labels(labels: &Vec<String>, filename: &str) -> Result<(), Error> {
+    let io_error = |e: io::Error| Error::Io(filename.to_string(), e.to_string());
+    let mut f = File::create(filename).map_err(io_error)?;
+    for label in labels {
+        writeln!(f, "{}", label).map_err(io_error)?;
+    }
+    Ok(())
+}
+
+pub fn load_labels(filename: &str) -> Result<Vec<String>, Error> {
+    let io_error = |e: io::Error| Error::Io(filename.to_string(), e.to_string());
+    let mut result: Vec<String> = Vec::new();
+    for line in read_lines(filename).map_err(io_error)? {
+        result.push(line.map_err(io_error)?.trim().to_string());
+    }
+    Ok(result)
+}
+
+/// The problems that can be found while parsing a data file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    // The file could not be read
+    Io(String),
+    // The field is not of the form index:value
+    InvalidField(String),
+    // The index is not a positive integer
+    InvalidIndex(String),
+    // The value is not a number
+    InvalidValue(String),
+    // The index is lower than the previous one of the record
+    UnsortedIndex(usize),
+    // The index appears twice in the record
+    DuplicateIndex(usize),
+    // The record doesn't have the same number of columns as the first one
+    InvalidColumnCount(usize, usize),
+    // The label column isn't one of the columns of the file
+    UnknownLabelColumn(String),
+}
+
+/// An error found while parsing a data file, located by its file and line number.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub file: String,
+    pub line: usize,
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: ", self.file, self.line)?;
+        match &self.kind {
+            ParseErrorKind::Io(e) => write!(f, "could not read the file: {}", e),
+            ParseErrorKind::InvalidField(c) => write!(f, "invalid field '{}', expected index:value", c),
+            ParseErrorKind::InvalidIndex(i) => write!(f, "invalid index '{}'", i),
+            ParseErrorKind::InvalidValue(v) => write!(f, "invalid value '{}'", v),
+            ParseErrorKind::UnsortedIndex(i) => write!(f, "index {} is not in ascending order", i),
+            ParseErrorKind::DuplicateIndex(i) => write!(f, "index {} appears twice", i),
+            ParseErrorKind::InvalidColumnCount(expected, found) => {
+                write!(f, "expected {} columns, found {}", expected, found)
+            }
+            ParseErrorKind::UnknownLabelColumn(c) => write!(f, "unknown label column '{}'", c),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl ParseErrorKind {
+    // Whether the error stops the parsing even when the malformed lines are skipped
+    fn is_fatal(&self) -> bool {
+        matches!(self, ParseErrorKind::Io(_) | ParseErrorKind::UnknownLabelColumn(_))
+    }
+}
+
+/// How malformed lines are handled while parsing a data file.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ParseMode {
+    // The first malformed line stops the parsing with an error
+    Strict,
+    // The malformed lines are skipped and counted
+    Lenient,
+}
+
+// Parses one line of a data file into its label and its row of characteristics, returns None if the
+// line holds no record.
+fn parse_line(line: &str) -> Result<Option<(String, Vec<f64>)>, ParseErrorKind> {
+    // Everything after a '#' is a comment
+    let record = match line.find('#') {
+        Some(start) => &line[..start],
+        None => line,
+    };
+    let mut fields = record.split_whitespace();
+    let label = match fields.next() {
+        Some(label) => label,
+        None => return Ok(None),
+    };
+    let class: String = label.to_string();
+
+    // the new row that will be pushed into the matrix
+    let mut row: Vec<f64> = Vec::new();
+    //The last pushed characteristic in the row of the matrix
+    let mut index: usize = 0;
+    for cell in fields {
+        let (car, value) = match cell.split_once(':') {
+            Some(car_value) => car_value,
+            None => return Err(ParseErrorKind::InvalidField(cell.to_string())),
+        };
+        // The query ids of ranking datasets aren't characteristics
+        if car == "qid" {
+            continue;
+        }
+        //Charcteristic's index
+        let car: usize = match car.parse() {
+            Ok(car) if car > 0 => car,
+            _ => return Err(ParseErrorKind::InvalidIndex(car.to_string())),
+        };
+        if car == index {
+            return Err(ParseErrorKind::DuplicateIndex(car));
+        }
+        if car < index {
+            return Err(ParseErrorKind::UnsortedIndex(car));
+        }
+        //The value linked to that characteristic in general it is a 1, 0s are left blank
+        let value: f64 = value
+            .parse()
+            .map_err(|_| ParseErrorKind::InvalidValue(value.to_string()))?;
+        //Filling the missed characteristics with 0 so that we construct a valid X matrix
+        //of all the characteristics
+        for _ in index + 1..car {
+            row.push(0.);
+        }
+        //the current index filled in the row + 1
+        index = car;
+        //Pushing the current characteristic into the row with index: index - 1
+        row.push(value);
+    }
+    Ok(Some((class, row)))
+}
+
+/// The records of a data file, parsed one line at a time so that the file never has to be held in
+/// memory.
+pub struct Records {
+    file: String,
+    lines: std::iter::Enumerate<io::Lines<io::BufReader<File>>>,
+    mode: ParseMode,
+    // Parses a line into its label and its row of characteristics, or None if it holds no record
+    parse: Box<dyn FnMut(&str) -> Result<Option<(String, Vec<f64>)>, ParseErrorKind>>,
+    skipped_lines: usize,
+    done: bool,
+}
+
+impl Records {
+    pub fn new(
+        data_file: &str,
+        mode: ParseMode,
+        parse: Box<dyn FnMut(&str) -> Result<Option<(String, Vec<f64>)>, ParseErrorKind>>,
+    ) -> Result<Records, ParseError> {
+        let lines = read_lines(Path::new(data_file)).map_err(|e| ParseError {
+            file: data_file.to_string(),
+            line: 0,
+            kind: ParseErrorKind::Io(e.to_string()),
+        })?;
+        Ok(Records {
+            file: data_file.to_string(),
+            lines: lines.enumerate(),
+            mode,
+            parse,
+            skipped_lines: 0,
+            done: false,
+        })
+    }
+
+    fn error(&self, line: usize, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            file: self.file.clone(),
+            line,
+            kind,
+        }
+    }
+}
+
+impl Iterator for Records {
+    type Item = Result<(String, Vec<f64>), ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        while let Some((i, line)) = self.lines.next() {
+            let kind = match line {
+                Ok(l) => match (self.parse)(&l) {
+                    Ok(Some(record)) => return Some(Ok(record)),
+                    Ok(None) => continue,
+                    Err(kind) => kind,
+                },
+                Err(e) => ParseErrorKind::Io(e.to_string()),
+            };
+            let error = self.error(i + 1, kind);
+            if self.mode == ParseMode::Strict || error.kind.is_fatal() {
+                self.done = true;
+                return Some(Err(error));
+            }
+            println!("Skipping {}", error);
+            self.skipped_lines += 1;
+        }
+        self.done = true;
+        if self.skipped_lines > 0 {
+            println!("Skipped {} malformed lines in {}", self.skipped_lines, self.file);
         }
+        None
     }
-    return result;
 }
 
-pub fn parse_data_file(data_file: &str) -> (Vec<Vec<f64>>, Vec<f64>) {
-    let lines: io::Lines<io::BufReader<File>> = read_lines(Path::new(data_file)).unwrap();
+/// Reads all the records into the matrix of characteristics and the vector of labels, the rows are
+/// squared up to the longest one.
+pub fn collect_records(records: Records) -> Result<(Vec<Vec<f64>>, Vec<String>), ParseError> {
     //Training data
     let mut x: Vec<Vec<f64>> = Vec::new();
-    //Predictions vecctor
-    let mut y: Vec<f64> = Vec::new();
-
-    let mut max_car: i32 = 0;
-    for line in lines {
-        // the new row that will be pushed into the matrix
-        let mut row: Vec<f64> = Vec::new();
-        if let Ok(l) = line {
-            let ls: Vec<&str> = l.split(' ').collect();
-            let class: f64 = ls[0].parse().unwrap();
-            if class == 1. {
-                y.push(1.);
-            } else {
-                y.push(-1.);
-            }
-            //The last pushed characteristic in the row of the matrix
-            let mut index: i32 = 0;
-            for i in 1..ls.len() {
-                let cell = ls[i];
-                let car_value: Vec<&str> = cell.split(':').collect();
-                if car_value.len() != 2 {
-                    println!("The file is malformated in the region : {}", cell);
-                    //TODO
-                    //Should be a warning and throw Error
-                }
-                //Charcteristic's index
-                let car: i32 = car_value[0].parse().unwrap();
-                //The value linked to that characteristic in general it is a 1, 0s are left blank
-                let value: f64 = car_value[1].parse().unwrap();
-                //Filling the missed characteristics with 0 so that we construct a valid X matrix
-                //of all the characteristics
-                for _ in index + 1..car {
-                    row.push(0.);
-                }
-                //the current index filled in the row + 1
-                index = car;
-                //Pushing the current characteristic into the row with index: index - 1
-                row.push(value);
-                //Keeping track of the longest row in the matrix so at the end we square it up
-                if index > max_car {
-                    max_car = index;
-                }
-            }
-            //pushing the new line into the x matrix
-            x.push(row);
+    //Labels of the records
+    let mut y: Vec<String> = Vec::new();
+
+    let mut max_car: usize = 0;
+    for record in records {
+        let (class, row) = record?;
+        y.push(class);
+        //Keeping track of the longest row in the matrix so at the end we square it up
+        if row.len() > max_car {
+            max_car = row.len();
         }
+        //pushing the new line into the x matrix
+        x.push(row);
     }
     //Squaring the matrix
     for i in 0..x.len() {
-        for _ in x[i].len()..max_car as usize {
+        for _ in x[i].len()..max_car {
             x[i].push(0.);
         }
     }
 
-    (x, y)
+    Ok((x, y))
+}
+
+/// Reads the records of a libsvm data file one at a time.
+pub fn libsvm_records(data_file: &str, mode: ParseMode) -> Result<Records, ParseError> {
+    Records::new(data_file, mode, Box::new(parse_line))
+}
+
+pub fn parse_data_file(
+    data_file: &str,
+    mode: ParseMode,
+) -> Result<(Vec<Vec<f64>>, Vec<String>), ParseError> {
+    collect_records(libsvm_records(data_file, mode)?)
+}
+
+/// Counts the complete lines of a file, ended by a newline. A missing file has no lines.
+pub fn count_complete_lines(filename: &str) -> io::Result<usize> {
+    match std::fs::read(filename) {
+        Ok(content) => Ok(content.iter().filter(|b| **b == b'\n').count()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e),
+    }
+}
+
+/// Drops the last line of a file if it was cut by an interruption, before its newline. A missing
+/// file is left missing.
+pub fn truncate_partial_line(filename: &str) -> io::Result<()> {
+    let content = match std::fs::read(filename) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let length = content.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+    if length < content.len() {
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(filename)?
+            .set_len(length as u64)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zqz::testing::temp_file;
+
+    // Writes the content to a temporary data file.
+    fn data_file(name: &str, content: &str) -> String {
+        let path = temp_file(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn record(label: &str, row: Vec<f64>) -> Option<(String, Vec<f64>)> {
+        Some((label.to_string(), row))
+    }
+
+    #[test]
+    fn parse_fields_separated_by_tabs() {
+        assert_eq!(parse_line("1\t1:0.5\t3:2"), Ok(record("1", vec![0.5, 0., 2.])));
+        assert_eq!(parse_line(" -1 \t 2:1 \t"), Ok(record("-1", vec![0., 1.])));
+    }
+
+    #[test]
+    fn parse_comments() {
+        assert_eq!(parse_line("1 1:1 # 2:1"), Ok(record("1", vec![1.])));
+        assert_eq!(parse_line("# a comment line"), Ok(None));
+        assert_eq!(parse_line(""), Ok(None));
+    }
+
+    #[test]
+    fn parse_skips_query_ids() {
+        assert_eq!(parse_line("2 qid:7 1:1 2:3"), Ok(record("2", vec![1., 3.])));
+        assert_eq!(parse_line("2 qid:7"), Ok(record("2", vec![])));
+    }
+
+    #[test]
+    fn parse_rejects_unsorted_and_duplicate_indices() {
+        assert_eq!(parse_line("1 3:1 2:1"), Err(ParseErrorKind::UnsortedIndex(2)));
+        assert_eq!(parse_line("1 2:1 2:1"), Err(ParseErrorKind::DuplicateIndex(2)));
+    }
+
+    #[test]
+    fn parse_rejects_malformed_fields() {
+        assert_eq!(
+            parse_line("1 1"),
+            Err(ParseErrorKind::InvalidField("1".to_string()))
+        );
+        assert_eq!(
+            parse_line("1 0:1"),
+            Err(ParseErrorKind::InvalidIndex("0".to_string()))
+        );
+        assert_eq!(
+            parse_line("1 1:a"),
+            Err(ParseErrorKind::InvalidValue("a".to_string()))
+        );
+    }
+
+    #[test]
+    fn strict_parsing_stops_at_the_first_malformed_line() {
+        let file = data_file("utils_strict", "1 1:1\n-1 2:1 1:1\n1 1:1\n");
+        let error = parse_data_file(&file, ParseMode::Strict).unwrap_err();
+        assert_eq!(error.line, 2);
+        assert_eq!(error.kind, ParseErrorKind::UnsortedIndex(1));
+    }
+
+    #[test]
+    fn lenient_parsing_counts_the_skipped_lines() {
+        let file = data_file(
+            "utils_lenient",
+            "1 1:1\n-1 2:1 1:1\n# comment\n\n1 x\n-1 2:2\n",
+        );
+        let mut records = libsvm_records(&file, ParseMode::Lenient).unwrap();
+        let parsed: Vec<(String, Vec<f64>)> =
+            records.by_ref().collect::<Result<_, _>>().unwrap();
+        assert_eq!(
+            parsed,
+            vec![
+                ("1".to_string(), vec![1.]),
+                ("-1".to_string(), vec![0., 2.])
+            ]
+        );
+        // The comment and the empty line aren't malformed
+        assert_eq!(records.skipped_lines, 2);
+    }
+
+    #[test]
+    fn collect_squares_up_the_rows() {
+        let file = data_file("utils_square", "1 3:1\n-1 1:2\n");
+        let (x, y) = parse_data_file(&file, ParseMode::Strict).unwrap();
+        assert_eq!(x, vec![vec![0., 0., 1.], vec![2., 0., 0.]]);
+        assert_eq!(y, vec!["1".to_string(), "-1".to_string()]);
+    }
+
+    #[test]
+    fn count_lines_without_changing_the_file() {
+        let file = data_file("utils_count", "1\n-1\n1");
+        assert_eq!(count_complete_lines(&file).unwrap(), 2);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "1\n-1\n1");
+        assert_eq!(count_complete_lines(&temp_file("utils_missing_file")).unwrap(), 0);
+    }
+
+    #[test]
+    fn truncate_the_partial_last_line() {
+        let file = data_file("utils_truncate", "1\n-1\n1");
+        truncate_partial_line(&file).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "1\n-1\n");
+        // A file of complete lines is left as it is
+        truncate_partial_line(&file).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "1\n-1\n");
+        let file = data_file("utils_truncate_single", "-");
+        truncate_partial_line(&file).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "");
+        truncate_partial_line(&temp_file("utils_missing_file")).unwrap();
+    }
+
+    #[test]
+    fn save_and_load_labels() {
+        let file = data_file("labels_round_trip", "");
+        let labels = vec!["yes".to_string(), "no".to_string()];
+        save_labels(&labels, &file).unwrap();
+        assert_eq!(load_labels(&file), Ok(labels));
+        let missing = std::path::Path::new(&temp_file("labels_missing")).join("predictions");
+        let missing = missing.to_str().unwrap();
+        assert!(matches!(load_labels(missing), Err(Error::Io(_, _))));
+        assert!(matches!(save_labels(&vec![], missing), Err(Error::Io(_, _))));
+    }
 }
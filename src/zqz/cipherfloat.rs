@@ -2,9 +2,11 @@
 use crate::zqz;
 use crate::PARAMS;
 use concrete::crypto_api;
-use std::ops::{Add, AddAssign, Mul, MulAssign, Sub, SubAssign};
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
 use std::rc::Rc;
+use zqz::functions;
 use zqz::keys::HomomorphicKey;
+use zqz::profile;
 
 /// An encrypted message.
 #[derive(Debug, Clone, PartialEq)]
@@ -13,7 +15,7 @@ pub struct Cipherfloat {
     pub(super) evaluation_key: Rc<HomomorphicKey>,
 }
 
-fn bs_ks<F: Fn(f64) -> f64>(
+pub(super) fn bs_ks<F: Fn(f64) -> f64>(
     cipherfloat: &crypto_api::LWE,
     bootstrapping_key: &crypto_api::LWEBSK,
     func: F,
@@ -23,8 +25,10 @@ fn bs_ks<F: Fn(f64) -> f64>(
     let res = cipherfloat
         .bootstrap_with_function(bootstrapping_key, func, encoder)
         .unwrap();
+    profile::count_bootstrap();
 
     if PARAMS.with_ks {
+        profile::count_keyswitch();
         let res_ks = res.keyswitch(keyswitching_key).unwrap();
         return res_ks;
     } else {
@@ -34,6 +38,7 @@ fn bs_ks<F: Fn(f64) -> f64>(
 
 impl Cipherfloat {
     pub fn bs_ks<F: Fn(f64) -> f64>(self, func: F) -> Cipherfloat {
+        profile::count_function();
         let res = bs_ks(
             &self.cipherfloat,
             &self.evaluation_key.bootstrapping,
@@ -47,12 +52,46 @@ impl Cipherfloat {
             evaluation_key: self.evaluation_key.clone(),
         }
     }
+
+    /// Bootstraps `func` into the interval [min, max] with `nb_bit_precision` bits of precision,
+    /// for the functions whose outputs don't fit the interval of the input. The padding of the
+    /// input is kept.
+    #[allow(dead_code)]
+    pub fn bs_ks_into<F: Fn(f64) -> f64>(
+        &self,
+        func: F,
+        min: f64,
+        max: f64,
+        nb_bit_precision: usize,
+    ) -> Cipherfloat {
+        let encoder = crypto_api::Encoder::new(
+            min,
+            max,
+            nb_bit_precision,
+            self.cipherfloat.encoder.nb_bit_padding,
+        )
+        .unwrap();
+        profile::count_function();
+        let res = bs_ks(
+            &self.cipherfloat,
+            &self.evaluation_key.bootstrapping,
+            func,
+            &encoder,
+            &self.evaluation_key.keyswitching,
+        );
+
+        Cipherfloat {
+            cipherfloat: res,
+            evaluation_key: self.evaluation_key.clone(),
+        }
+    }
 }
 // Adds two cipherfloats using the `+` operator.
 impl Add<&Cipherfloat> for &Cipherfloat {
     type Output = Cipherfloat;
 
     fn add(self, other: &Cipherfloat) -> Self::Output {
+        profile::count_addition();
         // addition
         let sum = self
             .cipherfloat
@@ -79,6 +118,7 @@ impl Add<f64> for &Cipherfloat {
     type Output = Cipherfloat;
 
     fn add(self, other: f64) -> Self::Output {
+        profile::count_add_constant();
         let res: crypto_api::LWE = self
             .cipherfloat
             .add_constant_dynamic_encoder(other)
@@ -112,6 +152,7 @@ impl Sub<&Cipherfloat> for &Cipherfloat {
     type Output = Cipherfloat;
 
     fn sub(self, other: &Cipherfloat) -> Self::Output {
+        profile::count_substraction();
         // subtraction
         let sub = self
             .cipherfloat
@@ -130,6 +171,7 @@ impl Sub<f64> for &Cipherfloat {
     type Output = Cipherfloat;
 
     fn sub(self, other: f64) -> Self::Output {
+        profile::count_add_constant();
         let res: crypto_api::LWE = self
             .cipherfloat
             .add_constant_dynamic_encoder(-other)
@@ -158,11 +200,30 @@ impl SubAssign<f64> for Cipherfloat {
     }
 }
 
-// Multiplies two cipherfloats using the `*` operator.
+// The squares of a multiplication can outgrow the interval of the operands, so they are
+// bootstrapped into an interval holding them, shared by both so that they can be substracted.
+fn squares_encoder(
+    posi: &crypto_api::LWE,
+    nega: &crypto_api::LWE,
+    encoder: &crypto_api::Encoder,
+) -> crypto_api::Encoder {
+    let (posi_min, posi_max) = functions::interval(&posi.encoder);
+    let (nega_min, nega_max) = functions::interval(&nega.encoder);
+    let max_square = [posi_min, posi_max, nega_min, nega_max]
+        .iter()
+        .map(|x| x * x / 4.)
+        .fold(0., f64::max);
+    functions::encoder_like(encoder, 0., max_square)
+}
+
+// Multiplies two cipherfloats using the `*` operator. The product is bootstrapped into the
+// interval of `self`: a product outside of it wraps around, so the operands have to be scaled
+// for their product to fit it.
 impl Mul<&Cipherfloat> for &Cipherfloat {
     type Output = Cipherfloat;
 
     fn mul(self, other: &Cipherfloat) -> Self::Output {
+        profile::count_multiplication();
         let posi = self
             .cipherfloat
             .add_with_padding_exact(&other.cipherfloat)
@@ -172,12 +233,14 @@ impl Mul<&Cipherfloat> for &Cipherfloat {
             .cipherfloat
             .sub_with_padding_exact(&other.cipherfloat)
             .unwrap();
-        
+
+        let square_encoder = squares_encoder(&posi, &nega, &self.cipherfloat.encoder);
+
         let mut res_posi = bs_ks(
             &posi,
             &self.evaluation_key.bootstrapping,
             |x| (x * x / 4.),
-            &self.cipherfloat.encoder,
+            &square_encoder,
             &self.evaluation_key.keyswitching,
         );
 
@@ -185,7 +248,7 @@ impl Mul<&Cipherfloat> for &Cipherfloat {
             &nega,
             &self.evaluation_key.bootstrapping,
             |x| (x * x / 4.),
-            &self.cipherfloat.encoder,
+            &square_encoder,
             &self.evaluation_key.keyswitching,
         );
 
@@ -212,6 +275,7 @@ impl Mul<f64> for &Cipherfloat {
     type Output = Cipherfloat;
 
     fn mul(self, other: f64) -> Self::Output {
+        profile::count_mul_constant();
         let res = bs_ks(
             &self.cipherfloat,
             &self.evaluation_key.bootstrapping,
@@ -227,6 +291,64 @@ impl Mul<f64> for &Cipherfloat {
     }
 }
 
+// Multiplies a cipherfloat by a small integer using the `*` operator. The ciphertext is multiplied
+// directly when the padding allows it, keeping one bit of padding for a later bootstrap, and the
+// interval of the result grows with the integer. Otherwise, it is bootstrapped as a `f64`.
+impl Mul<i32> for &Cipherfloat {
+    type Output = Cipherfloat;
+
+    fn mul(self, other: i32) -> Self::Output {
+        if other == -1 {
+            return -self;
+        }
+        let nb_bit_padding = (other.unsigned_abs() as f64).log2().ceil() as usize;
+        if other == 0 || nb_bit_padding >= self.cipherfloat.encoder.nb_bit_padding {
+            return self * other as f64;
+        }
+        profile::count_negation();
+        let res = self
+            .cipherfloat
+            .mul_constant_with_padding(
+                other as f64,
+                (1u64 << nb_bit_padding) as f64,
+                nb_bit_padding,
+            )
+            .unwrap();
+
+        Cipherfloat {
+            cipherfloat: res,
+            evaluation_key: self.evaluation_key.clone(),
+        }
+    }
+}
+
+// Divides a cipherfloat by a constant using the `/` operator, panics on a division by 0 whose
+// infinite inverse can't be bootstrapped.
+impl Div<f64> for &Cipherfloat {
+    type Output = Cipherfloat;
+
+    fn div(self, other: f64) -> Self::Output {
+        assert!(other != 0., "division of a cipherfloat by 0");
+        self * (1. / other)
+    }
+}
+
+// Negates a cipherfloat using the `-` operator, only the encoder is changed and there is no
+// bootstrap.
+impl Neg for &Cipherfloat {
+    type Output = Cipherfloat;
+
+    fn neg(self) -> Self::Output {
+        profile::count_negation();
+        let res = self.cipherfloat.opposite().unwrap();
+
+        Cipherfloat {
+            cipherfloat: res,
+            evaluation_key: self.evaluation_key.clone(),
+        }
+    }
+}
+
 // Multiplies two cipherfloats using the `*=` operator.
 impl MulAssign<&Cipherfloat> for Cipherfloat {
     fn mul_assign(&mut self, other: &Cipherfloat) {
@@ -242,3 +364,145 @@ impl MulAssign<f64> for Cipherfloat {
         self.cipherfloat = res.cipherfloat;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zqz::testing::*;
+
+    #[test]
+    fn encrypt_and_decrypt() {
+        check_constant(1, |e_a, _| e_a.clone(), |a, _| a);
+    }
+
+    #[test]
+    fn add() {
+        check_binary(1, |e_a, e_b| e_a + e_b, |a, b| a + b);
+    }
+
+    #[test]
+    fn add_assign() {
+        check_binary(
+            1,
+            |e_a, e_b| {
+                let mut e_c = e_a.clone();
+                e_c += e_b;
+                e_c
+            },
+            |a, b| a + b,
+        );
+    }
+
+    #[test]
+    fn add_constant() {
+        check_constant(1, |e_a, b| e_a + b, |a, b| a + b);
+    }
+
+    #[test]
+    fn sub() {
+        check_binary(1, |e_a, e_b| e_a - e_b, |a, b| a - b);
+    }
+
+    #[test]
+    fn sub_constant() {
+        check_constant(1, |e_a, b| e_a - b, |a, b| a - b);
+    }
+
+    #[test]
+    fn mul() {
+        check_binary(3, |e_a, e_b| e_a * e_b, |a, b| a * b);
+    }
+
+    #[test]
+    fn mul_assign() {
+        check_binary(
+            3,
+            |e_a, e_b| {
+                let mut e_c = e_a.clone();
+                e_c *= e_b;
+                e_c
+            },
+            |a, b| a * b,
+        );
+    }
+
+    #[test]
+    fn mul_constant() {
+        check_constant(1, |e_a, b| e_a * b, |a, b| a * b);
+    }
+
+    #[test]
+    fn neg() {
+        check_constant(1, |e_a, _| -e_a, |a, _| -a);
+    }
+
+    #[test]
+    fn neg_then_add() {
+        check_binary(1, |e_a, e_b| e_a + &(-e_b), |a, b| a - b);
+    }
+
+    #[test]
+    fn div_constant() {
+        check_constant(1, |e_a, b| e_a / (b.abs() + 1.), |a, b| a / (b.abs() + 1.));
+    }
+
+    #[test]
+    #[should_panic(expected = "division of a cipherfloat by 0")]
+    fn div_by_zero() {
+        let sk = zero_key();
+        let _ = &sk.encrypt_float_with_encoder(1., &encoder()) / 0.;
+    }
+
+    #[test]
+    fn mul_small_integer() {
+        // the error of the input is scaled by |k| along with the message
+        for k in [-2, -1, 0, 2, 3] {
+            let nb_operations = k.unsigned_abs().max(1) as usize;
+            check_constant(nb_operations, |e_a, _| e_a * k, |a, _| a * k as f64);
+        }
+    }
+
+    #[test]
+    fn bootstrap_function() {
+        check_constant(1, |e_a, _| e_a.clone().bs_ks(|x| x / 2.), |a, _| a / 2.);
+    }
+
+    #[test]
+    fn bootstrap_into_interval() {
+        let sk = zero_key();
+        let e_a = sk.encrypt_float_with_encoder(-0.5, &encoder());
+        let e_b = e_a.bs_ks_into(|x| 1. / (1. + (-x).exp()), 0., 1.1, 8);
+        assert_eq!(functions::interval(&e_b.cipherfloat.encoder).0, 0.);
+        assert_close(sk.decrypt_float(&e_b), 1. / (1. + 0.5f64.exp()), 4. * 1.1 / 256.);
+    }
+
+    #[test]
+    fn mul_with_squares_outside_the_interval() {
+        // (a + b)² / 4 = 30.25 doesn't fit the interval of the operands
+        let sk = zero_key();
+        let encoder = encoder();
+        let e_a = sk.encrypt_float_with_encoder(5.5, &encoder);
+        let e_b = sk.encrypt_float_with_encoder(0.5, &encoder);
+        let square_encoder = squares_encoder(
+            &e_a.cipherfloat.add_with_padding_exact(&e_b.cipherfloat).unwrap(),
+            &e_a.cipherfloat.sub_with_padding_exact(&e_b.cipherfloat).unwrap(),
+            &encoder,
+        );
+        assert!(functions::interval(&square_encoder).1 >= 30.25);
+        // The rounding of both squares, then of the product into the interval of the operands
+        let tolerance =
+            2. * functions::granularity(&square_encoder) + functions::granularity(&encoder);
+        assert_close(sk.decrypt_float(&(&e_a * &e_b)), 2.75, tolerance);
+    }
+
+    #[test]
+    #[ignore]
+    fn mul_with_real_keys() {
+        let sk = crate::zqz::keys::EncryptKey::new();
+        let encoder = encoder();
+        let e_a = sk.encrypt_float_with_encoder(0.75, &encoder);
+        let e_b = sk.encrypt_float_with_encoder(-0.5, &encoder);
+        assert_close(sk.decrypt_float(&(&e_a * &e_b)), -0.375, tolerance(3));
+        assert_close(sk.decrypt_float(&(&e_a + &e_b)), 0.25, tolerance(1));
+    }
+}
@@ -0,0 +1,265 @@
+//! A module containing an encrypted integer of Z/qZ, q being `PARAMS.modulo`.
+//!
+//! The integers are encoded exactly in [0, q) with the precision of the parameters, one value per
+//! integer. The operations consume the padding of the operands and bootstrap the result back to
+//! [0, q) with `round_modulo`, which reduces it modulo q and restores the padding: the arithmetic is
+//! exact as long as the noise stays below half the granularity.
+use crate::zqz;
+use crate::PARAMS;
+use concrete::crypto_api;
+use std::ops::{Add, Mul, Sub};
+use std::rc::Rc;
+use zqz::cipherfloat::{bs_ks, Cipherfloat};
+use zqz::keys::{EncryptKey, HomomorphicKey};
+use zqz::profile;
+use zqz::utils::round_modulo;
+
+/// An encrypted integer modulo `PARAMS.modulo`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CipherInt {
+    pub(super) cipherint: crypto_api::LWE,
+    pub(super) evaluation_key: Rc<HomomorphicKey>,
+}
+
+/// The encoder of the integers of [0, q).
+pub fn int_encoder() -> crypto_api::Encoder {
+    // The encoder adds a step past its maximum, so that [0, q - 1] has a step of exactly 1 and q
+    // wraps around to 0
+    crypto_api::Encoder::new(
+        0.,
+        (PARAMS.modulo - 1) as f64,
+        PARAMS.nb_bit_precision,
+        PARAMS.nb_bit_padding,
+    )
+    .unwrap()
+}
+
+// Bootstraps `func` of the ciphertext into the integers of [0, q), reduced modulo q.
+fn bs_ks_modulo<F: Fn(f64) -> f64>(
+    ciphertext: &crypto_api::LWE,
+    evaluation_key: &Rc<HomomorphicKey>,
+    func: F,
+) -> CipherInt {
+    let res = bs_ks(
+        ciphertext,
+        &evaluation_key.bootstrapping,
+        |x| round_modulo(func(x)),
+        &int_encoder(),
+        &evaluation_key.keyswitching,
+    );
+    CipherInt {
+        cipherint: res,
+        evaluation_key: evaluation_key.clone(),
+    }
+}
+
+#[allow(dead_code)]
+impl CipherInt {
+    /// Evaluates the lookup table `table` on the integer, its outputs are reduced modulo q.
+    pub fn lookup<F: Fn(u64) -> u64>(&self, table: F) -> CipherInt {
+        profile::count_function();
+        bs_ks_modulo(&self.cipherint, &self.evaluation_key, |x| {
+            table(round_modulo(x) as u64) as f64
+        })
+    }
+
+    /// Converts the integer to a cipherfloat of `encoder`, whose interval has to hold [0, q).
+    pub fn to_cipherfloat(&self, encoder: &crypto_api::Encoder) -> Cipherfloat {
+        profile::count_function();
+        let res = bs_ks(
+            &self.cipherint,
+            &self.evaluation_key.bootstrapping,
+            round_modulo,
+            encoder,
+            &self.evaluation_key.keyswitching,
+        );
+        Cipherfloat {
+            cipherfloat: res,
+            evaluation_key: self.evaluation_key.clone(),
+        }
+    }
+}
+
+/// Serializes the ciphertexts of the integers.
+pub fn to_bytes(ints: &[CipherInt]) -> Vec<u8> {
+    let ciphertexts: Vec<&crypto_api::LWE> = ints.iter().map(|c| &c.cipherint).collect();
+    bincode::serialize(&ciphertexts).unwrap()
+}
+
+/// Deserializes the ciphertexts serialized by `to_bytes`, they are evaluated with the keys of `sk`
+/// which have to be the ones they were encrypted with.
+pub fn from_bytes(bytes: &[u8], sk: &EncryptKey) -> Option<Vec<CipherInt>> {
+    let ciphertexts: Vec<crypto_api::LWE> = bincode::deserialize(bytes).ok()?;
+    Some(
+        ciphertexts
+            .into_iter()
+            .map(|cipherint| CipherInt {
+                cipherint,
+                evaluation_key: sk.evaluation.clone(),
+            })
+            .collect(),
+    )
+}
+
+#[allow(dead_code)]
+impl Cipherfloat {
+    /// Converts the cipherfloat to an integer, rounded to the closest one and reduced modulo q.
+    pub fn to_cipherint(&self) -> CipherInt {
+        profile::count_function();
+        bs_ks_modulo(&self.cipherfloat, &self.evaluation_key, |x| x)
+    }
+
+    /// The bit of the sign of the cipherfloat, 1 when it is positive and 0 otherwise.
+    pub fn to_bit(&self) -> CipherInt {
+        profile::count_function();
+        bs_ks_modulo(&self.cipherfloat, &self.evaluation_key, |x| {
+            (x > 0.) as u64 as f64
+        })
+    }
+}
+
+// Adds two integers modulo q using the `+` operator.
+impl Add<&CipherInt> for &CipherInt {
+    type Output = CipherInt;
+
+    fn add(self, other: &CipherInt) -> Self::Output {
+        profile::count_addition();
+        let sum = self
+            .cipherint
+            .add_with_padding_exact(&other.cipherint)
+            .unwrap();
+        bs_ks_modulo(&sum, &self.evaluation_key, |x| x)
+    }
+}
+
+// Adds a constant to an integer modulo q using the `+` operator.
+impl Add<u64> for &CipherInt {
+    type Output = CipherInt;
+
+    fn add(self, other: u64) -> Self::Output {
+        // The constant is added by the bootstrap
+        profile::count_function();
+        bs_ks_modulo(&self.cipherint, &self.evaluation_key, move |x| {
+            x + other as f64
+        })
+    }
+}
+
+// Substracts two integers modulo q using the `-` operator.
+impl Sub<&CipherInt> for &CipherInt {
+    type Output = CipherInt;
+
+    fn sub(self, other: &CipherInt) -> Self::Output {
+        // The difference is bootstrapped back to [0, q)
+        profile::count_substraction();
+        profile::count_function();
+        let sub = self
+            .cipherint
+            .sub_with_padding_exact(&other.cipherint)
+            .unwrap();
+        bs_ks_modulo(&sub, &self.evaluation_key, |x| x)
+    }
+}
+
+// Multiplies an integer by a constant modulo q using the `*` operator.
+impl Mul<i64> for &CipherInt {
+    type Output = CipherInt;
+
+    fn mul(self, other: i64) -> Self::Output {
+        profile::count_mul_constant();
+        bs_ks_modulo(&self.cipherint, &self.evaluation_key, move |x| {
+            round_modulo(x) * other as f64
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zqz::functions::granularity;
+    use crate::zqz::testing::*;
+
+    const Q: u64 = PARAMS.modulo as u64;
+
+    #[test]
+    fn encrypt_and_decrypt() {
+        let sk = zero_key();
+        for value in &[0, 1, Q / 2, Q - 1] {
+            assert_eq!(sk.decrypt_int(&sk.encrypt_int(*value)), *value);
+        }
+        assert_eq!(sk.decrypt_int(&sk.encrypt_int(Q + 3)), 3);
+    }
+
+    #[test]
+    fn add_and_sub_modulo() {
+        let sk = zero_key();
+        let (e_a, e_b) = (sk.encrypt_int(Q - 4), sk.encrypt_int(10));
+        assert_eq!(sk.decrypt_int(&(&e_a + &e_b)), 6);
+        assert_eq!(sk.decrypt_int(&(&e_b - &e_a)), 14);
+        assert_eq!(sk.decrypt_int(&(&e_a - &e_b)), Q - 14);
+        assert_eq!(sk.decrypt_int(&(&e_a + 5)), 1);
+    }
+
+    #[test]
+    fn mul_by_constants() {
+        let sk = zero_key();
+        let e_a = sk.encrypt_int(5);
+        assert_eq!(sk.decrypt_int(&(&e_a * 3)), 15);
+        assert_eq!(sk.decrypt_int(&(&e_a * -1)), Q - 5);
+        assert_eq!(sk.decrypt_int(&(&e_a * Q as i64)), 0);
+    }
+
+    #[test]
+    fn encoder_step_is_one() {
+        assert_close(granularity(&int_encoder()), 1., 1e-9);
+    }
+
+    #[test]
+    fn add_across_half_the_modulo() {
+        let sk = zero_key();
+        for (a, b) in &[(31, 1), (16, 16), (32, 0), (20, 25), (32, 31), (40, 30), (Q - 1, 1)] {
+            let (e_a, e_b) = (sk.encrypt_int(*a), sk.encrypt_int(*b));
+            assert_eq!(sk.decrypt_int(&(&e_a + &e_b)), (a + b) % Q, "{} + {}", a, b);
+        }
+    }
+
+    #[test]
+    fn lookup_across_half_the_modulo() {
+        let sk = zero_key();
+        for k in 0..Q {
+            let e_k = sk.encrypt_int(k);
+            assert_eq!(sk.decrypt_int(&e_k.lookup(|k| k)), k);
+            assert_eq!(sk.decrypt_int(&e_k.lookup(|k| Q - 1 - k)), Q - 1 - k);
+        }
+    }
+
+    #[test]
+    fn lookup_tables() {
+        let sk = zero_key();
+        let e_a = sk.encrypt_int(7);
+        assert_eq!(sk.decrypt_int(&e_a.lookup(|k| k * k)), 49 % Q);
+        assert_eq!(sk.decrypt_int(&e_a.lookup(|k| k % 2)), 1);
+    }
+
+    #[test]
+    fn conversions() {
+        let sk = zero_key();
+        let e_x = sk.encrypt_float_with_encoder(2.8, &encoder());
+        let e_k = e_x.to_cipherint();
+        assert_eq!(sk.decrypt_int(&e_k), 3);
+        let float_encoder = encoder_of(0., Q as f64);
+        assert_close(sk.decrypt_float(&e_k.to_cipherfloat(&float_encoder)), 3., 0.5);
+        let e_y = sk.encrypt_float_with_encoder(-0.5, &encoder());
+        assert_eq!(sk.decrypt_int(&e_x.to_bit()), 1);
+        assert_eq!(sk.decrypt_int(&e_y.to_bit()), 0);
+    }
+
+    #[test]
+    fn serialization() {
+        let sk = zero_key();
+        let ints = vec![sk.encrypt_int(0), sk.encrypt_int(Q - 1)];
+        let restored = from_bytes(&to_bytes(&ints), &sk).unwrap();
+        assert_eq!(restored, ints);
+        assert!(from_bytes(b"not ciphertexts", &sk).is_none());
+    }
+}
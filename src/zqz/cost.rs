@@ -0,0 +1,151 @@
+//! A module containing the cost model of the cipherfloat operators.
+use crate::PARAMS;
+use serde::Serialize;
+use std::ops::{Add, AddAssign, Mul, Sub};
+
+/// Number of bootstraps of the addition of two cipherfloats.
+pub const ADD_COST: u64 = 1;
+/// Number of bootstraps of the addition of a constant to a cipherfloat.
+pub const ADD_CONSTANT_COST: u64 = 0;
+/// Number of bootstraps of the substraction of two cipherfloats.
+pub const SUB_COST: u64 = 0;
+/// Number of bootstraps of the multiplication of two cipherfloats.
+pub const MUL_COST: u64 = 3;
+/// Number of bootstraps of the multiplication of a cipherfloat by a constant.
+pub const MUL_CONSTANT_COST: u64 = 1;
+/// Number of bootstraps of the negation of a cipherfloat, and of its multiplication by a small
+/// integer within the padding.
+pub const NEGATION_COST: u64 = 0;
+/// Number of bootstraps of the evaluation of a function with `bs_ks`.
+pub const FUNCTION_COST: u64 = 1;
+
+/// The number of cipherfloat operations performed by an algorithm.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct OperationCount {
+    pub additions: u64,
+    pub add_constants: u64,
+    pub substractions: u64,
+    pub multiplications: u64,
+    pub mul_constants: u64,
+    pub negations: u64,
+    pub functions: u64,
+}
+
+impl OperationCount {
+    /// The cost of the dot product of two ciphervectors of dimension `dim`.
+    pub fn dot_product(dim: usize) -> OperationCount {
+        OperationCount {
+            multiplications: dim as u64,
+            additions: dim.saturating_sub(1) as u64,
+            ..Default::default()
+        }
+    }
+
+    /// The number of bootstraps performed by the operations.
+    pub fn bootstraps(&self) -> u64 {
+        self.additions * ADD_COST
+            + self.add_constants * ADD_CONSTANT_COST
+            + self.substractions * SUB_COST
+            + self.multiplications * MUL_COST
+            + self.mul_constants * MUL_CONSTANT_COST
+            + self.negations * NEGATION_COST
+            + self.functions * FUNCTION_COST
+    }
+
+    /// The number of keyswitches performed by the operations, one after each bootstrap.
+    pub fn keyswitches(&self) -> u64 {
+        if PARAMS.with_ks {
+            self.bootstraps()
+        } else {
+            0
+        }
+    }
+}
+
+// Adds two operation counts using the `+` operator.
+impl Add<OperationCount> for OperationCount {
+    type Output = OperationCount;
+
+    fn add(self, other: OperationCount) -> Self::Output {
+        OperationCount {
+            additions: self.additions + other.additions,
+            add_constants: self.add_constants + other.add_constants,
+            substractions: self.substractions + other.substractions,
+            multiplications: self.multiplications + other.multiplications,
+            mul_constants: self.mul_constants + other.mul_constants,
+            negations: self.negations + other.negations,
+            functions: self.functions + other.functions,
+        }
+    }
+}
+
+// Computes the difference of two operation counts using the `-` operator.
+impl Sub<OperationCount> for OperationCount {
+    type Output = OperationCount;
+
+    fn sub(self, other: OperationCount) -> Self::Output {
+        OperationCount {
+            additions: self.additions - other.additions,
+            add_constants: self.add_constants - other.add_constants,
+            substractions: self.substractions - other.substractions,
+            multiplications: self.multiplications - other.multiplications,
+            mul_constants: self.mul_constants - other.mul_constants,
+            negations: self.negations - other.negations,
+            functions: self.functions - other.functions,
+        }
+    }
+}
+
+// Adds two operation counts using the `+=` operator.
+impl AddAssign<OperationCount> for OperationCount {
+    fn add_assign(&mut self, other: OperationCount) {
+        *self = *self + other;
+    }
+}
+
+// Repeats an operation count using the `*` operator.
+impl Mul<u64> for OperationCount {
+    type Output = OperationCount;
+
+    fn mul(self, other: u64) -> Self::Output {
+        OperationCount {
+            additions: self.additions * other,
+            add_constants: self.add_constants * other,
+            substractions: self.substractions * other,
+            multiplications: self.multiplications * other,
+            mul_constants: self.mul_constants * other,
+            negations: self.negations * other,
+            functions: self.functions * other,
+        }
+    }
+}
+
+/// The size in bytes of a cipherfloat, after the keyswitch if any.
+pub fn ciphertext_size() -> u64 {
+    let dimension = if PARAMS.with_ks {
+        PARAMS.lwe_setting.dimension
+    } else {
+        PARAMS.rlwe_setting.dimension * PARAMS.rlwe_setting.polynomial_size
+    };
+    // The mask and the body are 64 bits torus elements
+    ((dimension + 1) * 8) as u64
+}
+
+/// The size in bytes of the bootstrapping and keyswitching keys.
+pub fn keys_size() -> u64 {
+    let lwe_dimension = if PARAMS.with_ks {
+        PARAMS.lwe_setting.dimension
+    } else {
+        PARAMS.rlwe_setting.dimension * PARAMS.rlwe_setting.polynomial_size
+    };
+    let k = PARAMS.rlwe_setting.dimension;
+    let n = PARAMS.rlwe_setting.polynomial_size;
+    // The bootstrapping key is stored in the fourier domain, with 128 bits complex coefficients
+    let bootstrapping = lwe_dimension * (k + 1) * (k + 1) * PARAMS.bs_level * n * 16;
+    let keyswitching = if PARAMS.with_ks {
+        k * n * PARAMS.ks_level * (lwe_dimension + 1) * 8
+    } else {
+        0
+    };
+    (bootstrapping + keyswitching) as u64
+}
@@ -0,0 +1,239 @@
+//! A module containing a library of univariate functions evaluated on cipherfloats by bootstrapping.
+//!
+//! Every function bootstraps into an encoder sized for its own outputs: the function is evaluated
+//! on every value the input encoder can represent, and the output interval is the smallest one
+//! holding the results. The outputs keep the precision and the padding of the input.
+use crate::zqz;
+use concrete::crypto_api;
+use zqz::cipherfloat::{bs_ks, Cipherfloat};
+use zqz::profile;
+
+/// The interval [min, max] of the values represented by an encoder.
+pub fn interval(encoder: &crypto_api::Encoder) -> (f64, f64) {
+    (encoder.o, encoder.o + encoder.delta)
+}
+
+/// The difference between two consecutive values represented by an encoder.
+#[allow(dead_code)]
+pub fn granularity(encoder: &crypto_api::Encoder) -> f64 {
+    encoder.delta / (1u64 << encoder.nb_bit_precision) as f64
+}
+
+/// Builds the encoder of [min, max] with the precision and the padding of `encoder`. concrete
+/// extends the interval by one step, so that `max` doesn't wrap around to `min`.
+pub fn encoder_like(encoder: &crypto_api::Encoder, min: f64, max: f64) -> crypto_api::Encoder {
+    let (min, max) = if max - min > f64::EPSILON * max.abs().max(1.) {
+        (min, max)
+    } else {
+        // A constant output still needs an interval around it
+        (min - 1., max + 1.)
+    };
+    crypto_api::Encoder::new(min, max, encoder.nb_bit_precision, encoder.nb_bit_padding).unwrap()
+}
+
+/// The smallest interval holding the values of `func` on every value represented by `encoder`.
+#[allow(dead_code)]
+pub fn output_range<F: Fn(f64) -> f64>(encoder: &crypto_api::Encoder, func: F) -> (f64, f64) {
+    let (min, _) = interval(encoder);
+    let step = granularity(encoder);
+    let mut output_min: f64 = f64::MAX;
+    let mut output_max: f64 = f64::MIN;
+    for k in 0..(1u64 << encoder.nb_bit_precision) {
+        let y = func(min + k as f64 * step);
+        output_min = output_min.min(y);
+        output_max = output_max.max(y);
+    }
+    (output_min, output_max)
+}
+
+#[allow(dead_code)]
+impl Cipherfloat {
+    /// Evaluates `func` on the cipherfloat, into an encoder sized for its outputs.
+    pub fn apply<F: Fn(f64) -> f64>(&self, func: F) -> Cipherfloat {
+        let (min, max) = output_range(&self.cipherfloat.encoder, &func);
+        let encoder = encoder_like(&self.cipherfloat.encoder, min, max);
+        profile::count_function();
+        let res = bs_ks(
+            &self.cipherfloat,
+            &self.evaluation_key.bootstrapping,
+            func,
+            &encoder,
+            &self.evaluation_key.keyswitching,
+        );
+        Cipherfloat {
+            cipherfloat: res,
+            evaluation_key: self.evaluation_key.clone(),
+        }
+    }
+
+    /// 1 for the positive values and 0, -1 for the negative ones.
+    pub fn sign(&self) -> Cipherfloat {
+        self.apply(|x| if x >= 0. { 1. } else { -1. })
+    }
+
+    pub fn abs(&self) -> Cipherfloat {
+        self.apply(|x| x.abs())
+    }
+
+    pub fn relu(&self) -> Cipherfloat {
+        self.apply(|x| x.max(0.))
+    }
+
+    pub fn clamp(&self, min: f64, max: f64) -> Cipherfloat {
+        self.apply(move |x| x.max(min).min(max))
+    }
+
+    /// The logistic function 1 / (1 + e^-x).
+    pub fn sigmoid(&self) -> Cipherfloat {
+        self.apply(|x| 1. / (1. + (-x).exp()))
+    }
+
+    pub fn tanh(&self) -> Cipherfloat {
+        self.apply(|x| x.tanh())
+    }
+
+    pub fn exp(&self) -> Cipherfloat {
+        self.apply(|x| x.exp())
+    }
+
+    /// The natural logarithm, the values lower than the granularity of the encoder are raised to
+    /// it.
+    pub fn ln(&self) -> Cipherfloat {
+        let smallest = granularity(&self.cipherfloat.encoder);
+        self.apply(move |x| x.max(smallest).ln())
+    }
+
+    /// 1 / x, the values closer to 0 than the granularity of the encoder are pushed away to it
+    /// keeping their sign.
+    pub fn reciprocal(&self) -> Cipherfloat {
+        let smallest = granularity(&self.cipherfloat.encoder);
+        self.apply(move |x| {
+            if x >= 0. {
+                1. / x.max(smallest)
+            } else {
+                1. / x.min(-smallest)
+            }
+        })
+    }
+
+    /// The square root, the negative values are raised to 0.
+    pub fn sqrt(&self) -> Cipherfloat {
+        self.apply(|x| x.max(0.).sqrt())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zqz::testing::*;
+
+    // Checks an encrypted function against its f64 reference on random values of [min, max].
+    fn check<F, G>(min: f64, max: f64, cipher_func: F, plain_func: G)
+    where
+        F: Fn(&Cipherfloat) -> Cipherfloat,
+        G: Fn(f64) -> f64,
+    {
+        let encoder = encoder_of(min, max);
+        let input_step = granularity(&encoder);
+        check_operation(
+            &encoder,
+            (min, max),
+            1,
+            |e_x, _| cipher_func(&e_x[0]),
+            |[x, _]| plain_func(x),
+            // The input is rounded to the encoder, and the output to its own encoder
+            |[x, _], e_y| {
+                2. * granularity(&e_y.cipherfloat.encoder)
+                    + (plain_func(x + input_step) - plain_func(x - input_step)).abs()
+            },
+        );
+    }
+
+    #[test]
+    fn output_range_covers_the_outputs() {
+        let encoder = encoder_of(-2., 2.);
+        let (min, max) = output_range(&encoder, |x| x * x);
+        assert_eq!(min, 0.);
+        assert!(max >= 3.9 && max <= 4.);
+    }
+
+    #[test]
+    fn upper_bound_of_the_outputs_is_represented() {
+        // The outputs reach the upper bound of their interval, which doesn't wrap around to the
+        // lower one
+        let sk = zero_key();
+        let encoder = encoder_of(-2., 2.);
+        let e_x = sk.encrypt_float_with_encoder(1.5, &encoder);
+        let e_min = sk.encrypt_float_with_encoder(-2., &encoder);
+        for (e_y, y) in [(e_x.sign(), 1.), (e_x.clamp(-0.5, 1.), 1.), (e_min.abs(), 2.)] {
+            let tolerance = 2. * granularity(&e_y.cipherfloat.encoder);
+            assert_close(sk.decrypt_float(&e_y), y, tolerance);
+        }
+    }
+
+    #[test]
+    fn sign() {
+        let encoder = encoder_of(-2., 2.);
+        let input_step = granularity(&encoder);
+        check_operation(
+            &encoder,
+            (-2., 2.),
+            1,
+            |e_x, _| e_x[0].sign(),
+            |[x, _]| if x >= 0. { 1. } else { -1. },
+            // The inputs within one step of 0 can be rounded to either side and aren't checked
+            |[x, _], e_y| {
+                if x.abs() <= input_step {
+                    f64::INFINITY
+                } else {
+                    2. * granularity(&e_y.cipherfloat.encoder)
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn abs() {
+        check(-2., 2., |e_x| e_x.abs(), |x| x.abs());
+    }
+
+    #[test]
+    fn relu() {
+        check(-2., 2., |e_x| e_x.relu(), |x| x.max(0.));
+    }
+
+    #[test]
+    fn clamp() {
+        check(-2., 2., |e_x| e_x.clamp(-0.5, 1.), |x| x.max(-0.5).min(1.));
+    }
+
+    #[test]
+    fn sigmoid() {
+        check(-4., 4., |e_x| e_x.sigmoid(), |x| 1. / (1. + (-x).exp()));
+    }
+
+    #[test]
+    fn tanh() {
+        check(-2., 2., |e_x| e_x.tanh(), |x| x.tanh());
+    }
+
+    #[test]
+    fn exp() {
+        check(-2., 2., |e_x| e_x.exp(), |x| x.exp());
+    }
+
+    #[test]
+    fn ln() {
+        check(0.5, 4., |e_x| e_x.ln(), |x| x.ln());
+    }
+
+    #[test]
+    fn reciprocal() {
+        check(0.5, 4., |e_x| e_x.reciprocal(), |x| 1. / x);
+    }
+
+    #[test]
+    fn sqrt() {
+        check(0., 4., |e_x| e_x.sqrt(), |x| x.max(0.).sqrt());
+    }
+}
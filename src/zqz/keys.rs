@@ -99,6 +99,11 @@ impl EncryptKey {
             && Path::new(format!("{}_{}", prefix, KEYSWITCHING_FILE).as_str()).exists()
     }
 
+    /// The file holding the secret key with this prefix.
+    pub fn secret_key_file(prefix: &str) -> String {
+        format!("{}_{}", prefix, SECRET_FILE)
+    }
+
     /// Saves the encryption keys to files
     pub fn save_to_files(&self, prefix: &str) {
         self.secret
@@ -131,6 +136,7 @@ impl EncryptKey {
     }
 
     /// Encrypt the given message
+    #[allow(dead_code)]
     pub fn encrypt_float(&self, message: f64, min: f64, max: f64) -> zqz::cipherfloat::Cipherfloat {
         //let m = message % (PARAMS.modulo as f64);
         let encoder: crypto_api::Encoder = crypto_api::Encoder::new(
@@ -140,21 +146,43 @@ impl EncryptKey {
             PARAMS.nb_bit_padding,
         )
         .unwrap();
+        self.encrypt_float_with_encoder(message, &encoder)
+    }
+
+    /// Encrypt the given message with the given encoder
+    pub fn encrypt_float_with_encoder(
+        &self,
+        message: f64,
+        encoder: &crypto_api::Encoder,
+    ) -> zqz::cipherfloat::Cipherfloat {
         let ct: crypto_api::LWE =
-            crypto_api::LWE::encode_encrypt(&self.secret, message, &encoder).unwrap();
+            crypto_api::LWE::encode_encrypt(&self.secret, message, encoder).unwrap();
         zqz::cipherfloat::Cipherfloat {
             cipherfloat: ct,
             evaluation_key: self.evaluation.clone(),
         }
     }
     //
+    #[allow(dead_code)]
     pub fn encrypt_vector(&self, v: &Vec<f64>, min: f64, max: f64) -> zqz::vector::CipherVector {
+        let encoder: crypto_api::Encoder = crypto_api::Encoder::new(
+            min,
+            max,
+            PARAMS.nb_bit_precision,
+            PARAMS.nb_bit_padding,
+        )
+        .unwrap();
+        self.encrypt_vector_with_encoder(v, &encoder)
+    }
+
+    pub fn encrypt_vector_with_encoder(
+        &self,
+        v: &Vec<f64>,
+        encoder: &crypto_api::Encoder,
+    ) -> zqz::vector::CipherVector {
         let mut cv: Vec<zqz::cipherfloat::Cipherfloat> = Vec::new();
         for i in 0..v.len() {
-            let c: zqz::cipherfloat::Cipherfloat = self.encrypt_float(
-                v[i],
-                min,
-                max);
+            let c: zqz::cipherfloat::Cipherfloat = self.encrypt_float_with_encoder(v[i], encoder);
             cv.push(c);
         }
         zqz::vector::CipherVector {
@@ -164,11 +192,27 @@ impl EncryptKey {
         }
     }
 
+    #[allow(dead_code)]
     pub fn encrypt_matrix(
         &self,
         matrix: &Vec<Vec<f64>>,
         min: f64,
         max: f64,
+    ) -> zqz::matrix::CipherMatrix {
+        let encoder: crypto_api::Encoder = crypto_api::Encoder::new(
+            min,
+            max,
+            PARAMS.nb_bit_precision,
+            PARAMS.nb_bit_padding,
+        )
+        .unwrap();
+        self.encrypt_matrix_with_encoder(matrix, &encoder)
+    }
+
+    pub fn encrypt_matrix_with_encoder(
+        &self,
+        matrix: &Vec<Vec<f64>>,
+        encoder: &crypto_api::Encoder,
     ) -> zqz::matrix::CipherMatrix {
         //TODO check length of matrix
 
@@ -180,7 +224,7 @@ impl EncryptKey {
             let mut cmr: Vec<zqz::cipherfloat::Cipherfloat> = Vec::new();
             for j in 0..m {
                 let c: zqz::cipherfloat::Cipherfloat =
-                    self.encrypt_float(matrix[i][j], min, max);
+                    self.encrypt_float_with_encoder(matrix[i][j], encoder);
                 cmr.push(c);
             }
             cm.push(cmr);
@@ -223,6 +267,26 @@ impl EncryptKey {
         }
         return m;
     }
+    /// Encrypts the integer modulo `PARAMS.modulo`
+    #[allow(dead_code)]
+    pub fn encrypt_int(&self, message: u64) -> zqz::cipherint::CipherInt {
+        let message = zqz::utils::round_modulo(message as f64);
+        let ct: crypto_api::LWE =
+            crypto_api::LWE::encode_encrypt(&self.secret, message, &zqz::cipherint::int_encoder())
+                .unwrap();
+        zqz::cipherint::CipherInt {
+            cipherint: ct,
+            evaluation_key: self.evaluation.clone(),
+        }
+    }
+
+    /// Decrypts the integer, rounded to the closest one modulo `PARAMS.modulo`
+    #[allow(dead_code)]
+    pub fn decrypt_int(&self, ct: &zqz::cipherint::CipherInt) -> u64 {
+        let dec: f64 = ct.cipherint.decrypt_decode(&self.secret).unwrap();
+        zqz::utils::round_modulo(dec) as u64
+    }
+
     #[allow(dead_code)]
     /// We decrypt the cipherfloat
     pub fn decrypt_float(&self, ct: &zqz::cipherfloat::Cipherfloat) -> f64 {
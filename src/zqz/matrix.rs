@@ -1,8 +1,12 @@
 //! A module containing a ciphertext structure.
+use crate::clear;
 use crate::zqz;
+use clear::matrix::PlainMatrix;
+use clear::vector::PlainVector;
+use concrete::crypto_api;
 use std::ops::{Add, AddAssign, Mul, Sub, SubAssign};
 use std::rc::Rc;
-use zqz::keys::HomomorphicKey;
+use zqz::keys::{EncryptKey, HomomorphicKey};
 
 /// An encrypted message.
 #[derive(Debug, Clone, PartialEq)]
@@ -21,6 +25,56 @@ impl CipherMatrix {
             evaluation_key: self.evaluation_key.clone(),
         }
     }
+
+    /// The transposed matrix, the ciphertexts are only moved around.
+    pub fn transpose(&self) -> CipherMatrix {
+        let ciphermatrix: Vec<Vec<zqz::cipherfloat::Cipherfloat>> = (0..self.dim_m)
+            .map(|j| {
+                (0..self.dim_n)
+                    .map(|i| self.ciphermatrix[i][j].clone())
+                    .collect()
+            })
+            .collect();
+        CipherMatrix {
+            ciphermatrix,
+            dim_n: self.dim_m,
+            dim_m: self.dim_n,
+            evaluation_key: self.evaluation_key.clone(),
+        }
+    }
+
+    /// Serializes the ciphertexts of the matrix, without the evaluation key.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let ciphertexts: Vec<Vec<&crypto_api::LWE>> = self
+            .ciphermatrix
+            .iter()
+            .map(|row| row.iter().map(|c| &c.cipherfloat).collect())
+            .collect();
+        bincode::serialize(&ciphertexts).unwrap()
+    }
+
+    /// Deserializes the ciphertexts serialized by `to_bytes`, they are evaluated with the keys of
+    /// `sk` which have to be the ones they were encrypted with.
+    pub fn from_bytes(bytes: &[u8], sk: &EncryptKey) -> Option<CipherMatrix> {
+        let ciphertexts: Vec<Vec<crypto_api::LWE>> = bincode::deserialize(bytes).ok()?;
+        let ciphermatrix: Vec<Vec<zqz::cipherfloat::Cipherfloat>> = ciphertexts
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|cipherfloat| zqz::cipherfloat::Cipherfloat {
+                        cipherfloat,
+                        evaluation_key: sk.evaluation.clone(),
+                    })
+                    .collect()
+            })
+            .collect();
+        Some(CipherMatrix {
+            dim_n: ciphermatrix.len(),
+            dim_m: ciphermatrix.first().map_or(0, |row| row.len()),
+            ciphermatrix,
+            evaluation_key: sk.evaluation.clone(),
+        })
+    }
 }
 
 // Adds two ciphermatrix using the `+` operator.
@@ -107,8 +161,224 @@ impl Mul<&zqz::vector::CipherVector> for &CipherMatrix {
         }
         zqz::vector::CipherVector {
             ciphervector: result,
-            dim: other.dim,
+            dim: self.dim_n,
             evaluation_key: other.evaluation_key.clone(),
         }
     }
 }
+
+// Multiplies two ciphermatrix using the `*` operator.
+impl Mul<&CipherMatrix> for &CipherMatrix {
+    type Output = CipherMatrix;
+
+    fn mul(self, other: &CipherMatrix) -> Self::Output {
+        // The columns of other are the rows of its transpose
+        let other = other.transpose();
+        let mut result: Vec<Vec<zqz::cipherfloat::Cipherfloat>> = Vec::new();
+        for i in 0..self.dim_n {
+            let row = self.get_row(i);
+            let mut result_row: Vec<zqz::cipherfloat::Cipherfloat> = Vec::new();
+            for j in 0..other.dim_n {
+                result_row.push(&row * &other.get_row(j));
+            }
+            result.push(result_row);
+        }
+
+        CipherMatrix {
+            ciphermatrix: result,
+            dim_n: self.dim_n,
+            dim_m: other.dim_n,
+            evaluation_key: self.evaluation_key.clone(),
+        }
+    }
+}
+
+// Multiplies ciphermatrix with plainmatrix using the `*` operator, the products are multiplications
+// by constants.
+impl Mul<&PlainMatrix> for &CipherMatrix {
+    type Output = CipherMatrix;
+
+    fn mul(self, other: &PlainMatrix) -> Self::Output {
+        let mut result: Vec<Vec<zqz::cipherfloat::Cipherfloat>> = Vec::new();
+        for i in 0..self.dim_n {
+            let row: &Vec<zqz::cipherfloat::Cipherfloat> = &self.ciphermatrix[i];
+            let mut result_row: Vec<zqz::cipherfloat::Cipherfloat> = Vec::new();
+            for j in 0..other.dim_m {
+                // The zeros of the column are skipped, as in the product of a plainvector
+                let mut v: Option<zqz::cipherfloat::Cipherfloat> = None;
+                for k in 0..self.dim_m {
+                    if other.plainmatrix[k][j] == 0. {
+                        continue;
+                    }
+                    let m: zqz::cipherfloat::Cipherfloat = &row[k] * other.plainmatrix[k][j];
+                    v = Some(match v {
+                        Some(v) => &m + &v,
+                        None => m,
+                    });
+                }
+                result_row.push(v.unwrap_or_else(|| &row[0] * 0.));
+            }
+            result.push(result_row);
+        }
+
+        CipherMatrix {
+            ciphermatrix: result,
+            dim_n: self.dim_n,
+            dim_m: other.dim_m,
+            evaluation_key: self.evaluation_key.clone(),
+        }
+    }
+}
+
+// Multiplies ciphermatrix with plainvector using the `*` operator, the products are multiplications
+// by constants.
+impl Mul<&PlainVector> for &CipherMatrix {
+    type Output = zqz::vector::CipherVector;
+
+    fn mul(self, other: &PlainVector) -> Self::Output {
+        let result: Vec<zqz::cipherfloat::Cipherfloat> =
+            (0..self.dim_n).map(|i| &self.get_row(i) * other).collect();
+        zqz::vector::CipherVector {
+            ciphervector: result,
+            dim: self.dim_n,
+            evaluation_key: self.evaluation_key.clone(),
+        }
+    }
+}
+
+// Multiplies plainmatrix with ciphervector using the `*` operator, the products are multiplications
+// by constants and the zeros of the plainmatrix are skipped.
+impl Mul<&zqz::vector::CipherVector> for &PlainMatrix {
+    type Output = zqz::vector::CipherVector;
+
+    fn mul(self, other: &zqz::vector::CipherVector) -> Self::Output {
+        let result: Vec<zqz::cipherfloat::Cipherfloat> =
+            (0..self.dim_n).map(|i| other * &self.get_row(i)).collect();
+        zqz::vector::CipherVector {
+            ciphervector: result,
+            dim: self.dim_n,
+            evaluation_key: other.evaluation_key.clone(),
+        }
+    }
+}
+
+// Multiplies plainmatrix with ciphermatrix using the `*` operator, the products are multiplications
+// by constants.
+impl Mul<&CipherMatrix> for &PlainMatrix {
+    type Output = CipherMatrix;
+
+    fn mul(self, other: &CipherMatrix) -> Self::Output {
+        // (P * C) = (C^T * P^T)^T
+        (&other.transpose() * &self.transpose()).transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clear::matrix::PlainMatrix;
+    use crate::clear::vector::PlainVector;
+    use crate::zqz::functions::granularity;
+    use crate::zqz::testing::*;
+
+    const DIM_N: usize = 2;
+    const DIM_M: usize = 3;
+    // The products of the tests, dot products of at most DIM_M, fit in [-4, 4]
+    const PRODUCT_BOUND: f64 = 4.;
+
+    #[test]
+    fn matrix_operators_match_plain_matrices() {
+        let sk = zero_key();
+        let encoder = encoder();
+        let mut rng = rng();
+        for _ in 0..NB_CASES {
+            let a = random_matrix(&mut rng, DIM_N, DIM_M);
+            let b = random_matrix(&mut rng, DIM_N, DIM_M);
+            let (p_a, p_b) = (PlainMatrix::new(a.clone()), PlainMatrix::new(b.clone()));
+            let e_a = sk.encrypt_matrix_with_encoder(&a, &encoder);
+            let e_b = sk.encrypt_matrix_with_encoder(&b, &encoder);
+
+            let sum = sk.decrypt_matrix(&(&e_a + &e_b));
+            assert_matrix_close(&sum, &(&p_a + &p_b).plainmatrix, tolerance(1));
+            let difference = sk.decrypt_matrix(&(&e_a - &e_b));
+            assert_matrix_close(&difference, &(&p_a - &p_b).plainmatrix, tolerance(1));
+            let row = sk.decrypt_vector(&e_a.get_row(1));
+            assert_vector_close(&row, &p_a.get_row(1).plainvector, tolerance(1));
+        }
+    }
+
+    #[test]
+    fn matrix_products_match_plain_matrices() {
+        let sk = zero_key();
+        let encoder = encoder_of(-PRODUCT_BOUND, PRODUCT_BOUND);
+        let mut rng = rng();
+        for _ in 0..NB_CASES {
+            let a = random_matrix(&mut rng, DIM_N, DIM_M);
+            let b = random_matrix(&mut rng, DIM_M, DIM_N);
+            let v = random_vector(&mut rng, DIM_M);
+            let (p_a, p_b) = (PlainMatrix::new(a.clone()), PlainMatrix::new(b.clone()));
+            let p_v = PlainVector::new(v.clone());
+            let e_a = sk.encrypt_matrix_with_encoder(&a, &encoder);
+            let e_b = sk.encrypt_matrix_with_encoder(&b, &encoder);
+            let e_v = sk.encrypt_vector_with_encoder(&v, &encoder);
+            let expected = (&p_a * &p_b).plainmatrix;
+
+            let transpose = sk.decrypt_matrix(&e_a.transpose());
+            assert_matrix_close(&transpose, &p_a.transpose().plainmatrix, granularity(&encoder));
+            let product = sk.decrypt_vector(&(&e_a * &e_v));
+            let error = dot_product_error(&encoder, DIM_M);
+            assert_vector_close(&product, &(&p_a * &p_v).plainvector, error);
+            let product = sk.decrypt_matrix(&(&e_a * &e_b));
+            assert_eq!((product.len(), product[0].len()), (DIM_N, DIM_N));
+            assert_matrix_close(&product, &expected, error);
+            let error = constant_dot_product_error(&encoder, DIM_M);
+            let product = sk.decrypt_matrix(&(&e_a * &p_b));
+            assert_matrix_close(&product, &expected, error);
+            let product = sk.decrypt_matrix(&(&p_a * &e_b));
+            assert_matrix_close(&product, &expected, error);
+        }
+        // The zeros are skipped, down to an encryption of 0 for a column of zeros
+        let e_a = sk.encrypt_matrix_with_encoder(&vec![vec![0.5, -1.], vec![0.25, 1.]], &encoder);
+        let p_b = PlainMatrix::new(vec![vec![2., 0.], vec![0., 0.]]);
+        let product = sk.decrypt_matrix(&(&e_a * &p_b));
+        let expected = vec![vec![1., 0.], vec![0.5, 0.]];
+        assert_matrix_close(&product, &expected, constant_dot_product_error(&encoder, 1));
+    }
+
+    #[test]
+    fn mixed_products_match_plain_matrices() {
+        let sk = zero_key();
+        let encoder = encoder_of(-PRODUCT_BOUND, PRODUCT_BOUND);
+        let mut rng = rng();
+        for _ in 0..NB_CASES {
+            let a = random_matrix(&mut rng, DIM_N, DIM_M);
+            let v = random_vector(&mut rng, DIM_M);
+            let w = random_vector(&mut rng, DIM_N);
+            let p_a = PlainMatrix::new(a.clone());
+            let (p_v, p_w) = (PlainVector::new(v.clone()), PlainVector::new(w.clone()));
+            let e_a = sk.encrypt_matrix_with_encoder(&a, &encoder);
+            let e_v = sk.encrypt_vector_with_encoder(&v, &encoder);
+
+            let error = constant_dot_product_error(&encoder, DIM_M);
+            let product = sk.decrypt_vector(&(&e_a * &p_v));
+            assert_vector_close(&product, &(&p_a * &p_v).plainvector, error);
+            let product = sk.decrypt_vector(&(&p_a * &e_v));
+            assert_vector_close(&product, &(&p_a * &p_v).plainvector, error);
+            // The product of the transpose, as in the gradient of the trainer
+            let e_w = sk.encrypt_vector_with_encoder(&w, &encoder);
+            let product = sk.decrypt_vector(&(&e_a.transpose() * &e_w));
+            let expected = (&p_a.transpose() * &p_w).plainvector;
+            assert_vector_close(&product, &expected, dot_product_error(&encoder, DIM_N));
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn product_with_real_keys() {
+        let sk = crate::zqz::keys::EncryptKey::new();
+        let encoder = encoder();
+        let e_a = sk.encrypt_matrix_with_encoder(&vec![vec![1., 0.5], vec![-0.5, 0.25]], &encoder);
+        let e_v = sk.encrypt_vector_with_encoder(&vec![0.5, -1.], &encoder);
+        let product = sk.decrypt_vector(&(&e_a * &e_v));
+        assert_vector_close(&product, &vec![0., -0.5], tolerance(4 * 2));
+    }
+}
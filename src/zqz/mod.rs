@@ -2,10 +2,17 @@
 use concrete::crypto_api;
 
 pub mod cipherfloat;
+pub mod cipherint;
+pub mod cost;
+pub mod functions;
 pub mod matrix;
+pub mod polynomial;
 pub mod vector;
 pub mod keys;
+pub mod profile;
 pub mod utils;
+#[cfg(test)]
+pub mod testing;
 
 // A structure representing the parameters of the
 pub struct Parameters {
@@ -0,0 +1,365 @@
+//! A module evaluating polynomials on cipherfloats with few ciphertext multiplications.
+use crate::zqz;
+use zqz::cipherfloat::Cipherfloat;
+use zqz::functions::interval;
+use zqz::vector::CipherVector;
+
+// The number of points sampled by the least squares approximations.
+const NB_FIT_POINTS: usize = 256;
+
+/// A polynomial c_0 + c_1 x + ... + c_n x^n.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polynomial {
+    pub coefficients: Vec<f64>,
+}
+
+// A cipherfloat plus a constant, the constants are added for free when the value is needed.
+struct Affine {
+    cipher: Option<Cipherfloat>,
+    constant: f64,
+}
+
+impl Affine {
+    fn add(self, other: Affine) -> Affine {
+        let cipher = match (self.cipher, other.cipher) {
+            (Some(a), Some(b)) => Some(&a + &b),
+            (a, b) => a.or(b),
+        };
+        Affine {
+            cipher,
+            constant: self.constant + other.constant,
+        }
+    }
+
+    // Multiplies by `y`, the constant becomes a multiplication by a constant.
+    fn mul(self, y: &Cipherfloat) -> Affine {
+        let by_cipher = Affine {
+            cipher: self.cipher.map(|c| &c * y),
+            constant: 0.,
+        };
+        let by_constant = Affine {
+            cipher: if self.constant != 0. {
+                Some(y * self.constant)
+            } else {
+                None
+            },
+            constant: 0.,
+        };
+        by_cipher.add(by_constant)
+    }
+
+    // The value as a cipherfloat, `x` gives a ciphertext to a constant value.
+    fn into_cipherfloat(self, x: &Cipherfloat) -> Cipherfloat {
+        let cipher = match self.cipher {
+            Some(cipher) => cipher,
+            None => x * 0.,
+        };
+        if self.constant != 0. {
+            &cipher + self.constant
+        } else {
+            cipher
+        }
+    }
+}
+
+impl Polynomial {
+    pub fn new(coefficients: Vec<f64>) -> Polynomial {
+        Polynomial { coefficients }
+    }
+
+    pub fn degree(&self) -> usize {
+        self.coefficients.len().saturating_sub(1)
+    }
+
+    /// Evaluates the polynomial on `x` with Horner's method.
+    pub fn eval(&self, x: f64) -> f64 {
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(0., |result, c| result * x + c)
+    }
+
+    /// The polynomial q(t) = p(scale * t).
+    pub fn scaled(&self, scale: f64) -> Polynomial {
+        let mut factor: f64 = 1.;
+        let mut coefficients: Vec<f64> = Vec::new();
+        for c in &self.coefficients {
+            coefficients.push(c * factor);
+            factor *= scale;
+        }
+        Polynomial::new(coefficients)
+    }
+
+    // The size of the blocks of Paterson-Stockmeyer minimizing the number of ciphertext
+    // multiplications, k - 1 to compute x^2 ... x^k and one per block but the last.
+    fn block_size(&self) -> usize {
+        let nb_coefficients = self.degree() + 1;
+        (1..=nb_coefficients)
+            .min_by_key(|k| k - 1 + (nb_coefficients + k - 1) / k - 1)
+            .unwrap()
+    }
+
+    /// Evaluates the polynomial on the cipherfloat with the Paterson-Stockmeyer algorithm: the
+    /// powers x^1 ... x^k are computed once, the blocks of k coefficients are combined with
+    /// multiplications by constants and the blocks are combined with Horner's method in x^k.
+    ///
+    /// The input of a polynomial of degree 2 or more is first scaled to [-1, 1] so that its powers
+    /// stay in its interval, the partial results and the value of the polynomial have to fit the
+    /// interval of the input.
+    pub fn eval_cipherfloat(&self, x: &Cipherfloat) -> Cipherfloat {
+        if self.coefficients.is_empty() {
+            return x * 0.;
+        }
+        let (min, max) = interval(&x.cipherfloat.encoder);
+        let bound = min.abs().max(max.abs());
+        let (polynomial, t) = if bound > 1. && self.degree() > 1 {
+            (self.scaled(bound), x / bound)
+        } else {
+            (self.clone(), x.clone())
+        };
+
+        let k = polynomial.block_size();
+        // powers[i] = t^(i + 1)
+        let mut powers: Vec<Cipherfloat> = vec![t.clone()];
+        for i in 1..k {
+            let power = &powers[i - 1] * &t;
+            powers.push(power);
+        }
+        let blocks: Vec<Affine> = polynomial
+            .coefficients
+            .chunks(k)
+            .map(|block| {
+                let mut value = Affine {
+                    cipher: None,
+                    constant: block[0],
+                };
+                for i in 1..block.len() {
+                    if block[i] != 0. {
+                        value = value.add(Affine {
+                            cipher: Some(&powers[i - 1] * block[i]),
+                            constant: 0.,
+                        });
+                    }
+                }
+                value
+            })
+            .collect();
+
+        let mut blocks = blocks.into_iter().rev();
+        let mut result = blocks.next().unwrap();
+        for block in blocks {
+            result = result.mul(&powers[k - 1]).add(block);
+        }
+        result.into_cipherfloat(&t)
+    }
+
+    /// Evaluates the polynomial on every value of the ciphervector.
+    #[allow(dead_code)]
+    pub fn eval_ciphervector(&self, v: &CipherVector) -> CipherVector {
+        CipherVector {
+            ciphervector: v
+                .ciphervector
+                .iter()
+                .map(|x| self.eval_cipherfloat(x))
+                .collect(),
+            dim: v.dim,
+            evaluation_key: v.evaluation_key.clone(),
+        }
+    }
+
+    /// The least squares approximation of degree `degree` of `func` on [min, max].
+    #[allow(dead_code)]
+    pub fn fit<F: Fn(f64) -> f64>(func: F, min: f64, max: f64, degree: usize) -> Polynomial {
+        // The fit is computed on t = x / bound in [-1, 1], which keeps the system well conditioned
+        let bound = min.abs().max(max.abs());
+        let n = degree + 1;
+        // The normal equations A^T A c = A^T y of the Vandermonde matrix A
+        let mut system: Vec<Vec<f64>> = vec![vec![0.; n + 1]; n];
+        for p in 0..NB_FIT_POINTS {
+            let x = min + (max - min) * p as f64 / (NB_FIT_POINTS - 1) as f64;
+            let t = x / bound;
+            let y = func(x);
+            let powers: Vec<f64> = (0..n).map(|i| t.powi(i as i32)).collect();
+            for i in 0..n {
+                for j in 0..n {
+                    system[i][j] += powers[i] * powers[j];
+                }
+                system[i][n] += powers[i] * y;
+            }
+        }
+        Polynomial::new(solve(system)).scaled(1. / bound)
+    }
+
+    /// The least squares approximation of the sigmoid 1 / (1 + e^-x) on [min, max], of degree 3, 5
+    /// or 7 usually.
+    #[allow(dead_code)]
+    pub fn sigmoid(degree: usize, min: f64, max: f64) -> Polynomial {
+        Polynomial::fit(|x| 1. / (1. + (-x).exp()), min, max, degree)
+    }
+}
+
+// Solves the linear system of the augmented matrix by Gaussian elimination with partial pivoting.
+#[allow(dead_code)]
+fn solve(mut system: Vec<Vec<f64>>) -> Vec<f64> {
+    let n = system.len();
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|a, b| {
+                system[*a][col]
+                    .abs()
+                    .partial_cmp(&system[*b][col].abs())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .unwrap();
+        system.swap(col, pivot);
+        for row in col + 1..n {
+            let factor = system[row][col] / system[col][col];
+            for j in col..=n {
+                system[row][j] -= factor * system[col][j];
+            }
+        }
+    }
+    let mut solution: Vec<f64> = vec![0.; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|j| system[row][j] * solution[j]).sum();
+        solution[row] = (system[row][n] - sum) / system[row][row];
+    }
+    solution
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zqz::functions::granularity;
+    use crate::zqz::testing::*;
+    use concrete::crypto_api;
+
+    // The greatest difference between the polynomial and the sigmoid on [min, max].
+    fn sigmoid_error(polynomial: &Polynomial, min: f64, max: f64) -> f64 {
+        (0..=100)
+            .map(|i| min + (max - min) * i as f64 / 100.)
+            .map(|x| (polynomial.eval(x) - 1. / (1. + (-x).exp())).abs())
+            .fold(0., f64::max)
+    }
+
+    // The error of the evaluation on a cipherfloat of `encoder`, following the operations of
+    // Paterson-Stockmeyer on values of at most 1 and coefficients of at most 1, so that the error
+    // of an operand isn't amplified.
+    fn eval_error(polynomial: &Polynomial, encoder: &crypto_api::Encoder) -> f64 {
+        let (min, max) = interval(encoder);
+        let bound = min.abs().max(max.abs());
+        // The rounding of the encryption, then of the scaling to [-1, 1]
+        let mut input = granularity(encoder) / 2.;
+        if bound > 1. && polynomial.degree() > 1 {
+            input += mul_constant_error(encoder);
+        }
+        let k = polynomial.block_size();
+        let mut powers: Vec<f64> = vec![input];
+        for i in 1..k {
+            powers.push(powers[i - 1] + input + mul_error(encoder));
+        }
+        let blocks: Vec<f64> = polynomial
+            .coefficients
+            .chunks(k)
+            .map(|block| {
+                (1..block.len())
+                    .filter(|i| block[*i] != 0.)
+                    .map(|i| {
+                        block[i].abs() * powers[i - 1]
+                            + mul_constant_error(encoder)
+                            + add_error(encoder)
+                    })
+                    .sum()
+            })
+            .collect();
+        // Horner's method in x^k: the block times x^k, its constant times x^k, and their sum with
+        // the next block
+        let mut blocks = blocks.into_iter().rev();
+        let mut result = blocks.next().unwrap();
+        for block in blocks {
+            result += powers[k - 1] + mul_error(encoder);
+            result += powers[k - 1] + mul_constant_error(encoder);
+            result += block + 2. * add_error(encoder);
+        }
+        // The rounding of the decryption
+        result + granularity(encoder) / 2.
+    }
+
+    // Checks the evaluation on random cipherfloats of [-1, 1], the polynomial having to stay in
+    // [-1, 1] there.
+    fn check_eval(polynomial: &Polynomial) {
+        let encoder = encoder_of(-VALUE_BOUND, VALUE_BOUND);
+        let tolerance = eval_error(polynomial, &encoder);
+        check_operation(
+            &encoder,
+            (-VALUE_BOUND, VALUE_BOUND),
+            1,
+            |e_x, _| polynomial.eval_cipherfloat(&e_x[0]),
+            |[x, _]| polynomial.eval(x),
+            |_, _| tolerance,
+        );
+    }
+
+    #[test]
+    fn eval_with_horner() {
+        let polynomial = Polynomial::new(vec![1., -2., 0., 3.]);
+        assert_eq!(polynomial.eval(2.), 1. - 4. + 24.);
+        assert_eq!(polynomial.degree(), 3);
+    }
+
+    #[test]
+    fn fit_recovers_polynomials() {
+        let expected = Polynomial::new(vec![0.5, -1., 0.25, 2.]);
+        let fitted = Polynomial::fit(|x| expected.eval(x), -3., 3., 3);
+        for i in 0..4 {
+            assert!((fitted.coefficients[i] - expected.coefficients[i]).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn sigmoid_approximations_improve_with_the_degree() {
+        let errors: Vec<f64> = [3, 5, 7]
+            .iter()
+            .map(|degree| sigmoid_error(&Polynomial::sigmoid(*degree, -4., 4.), -4., 4.))
+            .collect();
+        assert!(errors[0] > errors[1] && errors[1] > errors[2]);
+        assert!(errors[2] < 0.01);
+    }
+
+    #[test]
+    fn paterson_stockmeyer_block_size() {
+        // x^2 then (c_0 + c_1 x) + (c_2 + c_3 x) x^2
+        assert_eq!(Polynomial::new(vec![1.; 4]).block_size(), 2);
+        // c_0 + c_1 x needs a single multiplication by a constant
+        assert_eq!(Polynomial::new(vec![1.; 2]).block_size(), 1);
+        // x^2, x^3 then three blocks of three coefficients combined in x^3
+        assert_eq!(Polynomial::new(vec![1.; 9]).block_size(), 3);
+    }
+
+    #[test]
+    fn eval_on_cipherfloats() {
+        check_eval(&Polynomial::new(vec![0.5, 0.25, 0., -0.125]));
+    }
+
+    #[test]
+    fn eval_combining_several_blocks() {
+        let polynomial = Polynomial::new(vec![
+            0.25, 0.125, -0.125, 0.125, 0.0625, -0.0625, 0.125, 0., -0.125,
+        ]);
+        assert_eq!(polynomial.block_size(), 3);
+        assert_eq!(polynomial.coefficients.chunks(3).count(), 3);
+        check_eval(&polynomial);
+    }
+
+    #[test]
+    fn eval_on_ciphervectors() {
+        let sk = zero_key();
+        let encoder = encoder_of(-VALUE_BOUND, VALUE_BOUND);
+        let polynomial = Polynomial::new(vec![0., 1., 0.5]);
+        let v = vec![0.5, -0.5, 0.25];
+        let e_v = sk.encrypt_vector_with_encoder(&v, &encoder);
+        let result = sk.decrypt_vector(&polynomial.eval_ciphervector(&e_v));
+        let expected: Vec<f64> = v.iter().map(|x| polynomial.eval(*x)).collect();
+        assert_vector_close(&result, &expected, eval_error(&polynomial, &encoder));
+    }
+}
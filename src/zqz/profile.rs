@@ -0,0 +1,125 @@
+//! A module counting the cipherfloat operations and profiling the phases of a computation.
+//!
+//! The operations are counted in the categories of the cost model of `zqz::cost`, so that a
+//! profiled run can be compared to its estimate.
+use crate::zqz;
+use serde::Serialize;
+use std::cell::{Cell, RefCell};
+use std::ops::Sub;
+use zqz::cost::OperationCount;
+
+thread_local! {
+    static COUNTERS: Cell<Counters> = Cell::new(Counters::default());
+    static PHASES: RefCell<Vec<Phase>> = RefCell::new(Vec::new());
+}
+
+/// The number of operations performed on cipherfloats since the start of the thread.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Counters {
+    pub bootstraps: u64,
+    pub keyswitches: u64,
+    pub operations: OperationCount,
+}
+
+// Computes the operations performed between two snapshots using the `-` operator.
+impl Sub<Counters> for Counters {
+    type Output = Counters;
+
+    fn sub(self, other: Counters) -> Self::Output {
+        Counters {
+            bootstraps: self.bootstraps - other.bootstraps,
+            keyswitches: self.keyswitches - other.keyswitches,
+            operations: self.operations - other.operations,
+        }
+    }
+}
+
+/// A named phase of the computation, with its duration in seconds and the operations it performed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Phase {
+    pub name: String,
+    pub duration: f64,
+    pub counters: Counters,
+}
+
+/// The profiling report of the whole computation.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+    pub phases: Vec<Phase>,
+    pub total: Counters,
+}
+
+// Updates the counters of the thread.
+fn count<F: FnOnce(&mut Counters)>(update: F) {
+    COUNTERS.with(|counters| {
+        let mut value = counters.get();
+        update(&mut value);
+        counters.set(value);
+    });
+}
+
+pub(super) fn count_bootstrap() {
+    count(|counters| counters.bootstraps += 1);
+}
+
+pub(super) fn count_keyswitch() {
+    count(|counters| counters.keyswitches += 1);
+}
+
+pub(super) fn count_addition() {
+    count(|counters| counters.operations.additions += 1);
+}
+
+pub(super) fn count_add_constant() {
+    count(|counters| counters.operations.add_constants += 1);
+}
+
+pub(super) fn count_substraction() {
+    count(|counters| counters.operations.substractions += 1);
+}
+
+pub(super) fn count_multiplication() {
+    count(|counters| counters.operations.multiplications += 1);
+}
+
+pub(super) fn count_mul_constant() {
+    count(|counters| counters.operations.mul_constants += 1);
+}
+
+pub(super) fn count_negation() {
+    count(|counters| counters.operations.negations += 1);
+}
+
+pub(super) fn count_function() {
+    count(|counters| counters.operations.functions += 1);
+}
+
+/// Takes a snapshot of the operation counters.
+pub fn counters() -> Counters {
+    COUNTERS.with(|counters| counters.get())
+}
+
+/// Records a phase of the computation, called by `measure_duration!`.
+pub fn record_phase(name: &str, duration: f64, counters: Counters) {
+    PHASES.with(|phases| {
+        phases.borrow_mut().push(Phase {
+            name: name.trim().to_string(),
+            duration,
+            counters,
+        })
+    });
+}
+
+/// Builds the report of the phases recorded so far.
+pub fn report() -> Report {
+    Report {
+        phases: PHASES.with(|phases| phases.borrow().clone()),
+        total: counters(),
+    }
+}
+
+/// Saves the report of the phases recorded so far as JSON.
+pub fn save_report(filename: &str) -> std::io::Result<()> {
+    let json = serde_json::to_string_pretty(&report()).unwrap();
+    std::fs::write(filename, json)
+}
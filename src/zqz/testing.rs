@@ -0,0 +1,211 @@
+//! A module containing the helpers of the tests of the encrypted computations.
+//!
+//! The keys built by `EncryptKey::new_zero` are filled with zeros instead of noise and are
+//! generated almost instantly, so the operators can be checked quickly and deterministically. The
+//! tests with real keys are slow, they are ignored by default and run with
+//! `cargo test -- --ignored`.
+use crate::reg;
+use crate::zqz;
+use crate::PARAMS;
+use concrete::crypto_api;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use reg::planner::max_nb_bit_precision;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use zqz::cipherfloat::Cipherfloat;
+use zqz::functions::{encoder_like, granularity, interval};
+use zqz::keys::EncryptKey;
+
+// The bound of the interval of the encoder, large enough to hold the dot products of the tests.
+pub const BOUND: f64 = 8.;
+// The bound of the random values of the tests.
+pub const VALUE_BOUND: f64 = 1.;
+// The number of random cases of every property test.
+pub const NB_CASES: usize = 8;
+
+/// Builds the key without noise of the fast tests.
+pub fn zero_key() -> EncryptKey {
+    EncryptKey::new_zero()
+}
+
+/// A path of the temporary directory, unique to the process and to the call so that the tests
+/// running concurrently don't share files.
+pub fn temp_file(name: &str) -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let unique = format!(
+        "{}_{}_{}",
+        name,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    );
+    std::env::temp_dir().join(unique).to_str().unwrap().to_string()
+}
+
+/// Builds the random generator of a test, seeded so that a failure can be replayed.
+pub fn rng() -> StdRng {
+    StdRng::seed_from_u64(0x5eed)
+}
+
+/// Builds the encoder of [-BOUND, BOUND] with the greatest precision the bootstrap allows.
+pub fn encoder() -> crypto_api::Encoder {
+    encoder_of(-BOUND, BOUND)
+}
+
+/// Builds the encoder of [min, max] with the greatest precision the bootstrap allows.
+pub fn encoder_of(min: f64, max: f64) -> crypto_api::Encoder {
+    crypto_api::Encoder::new(min, max, nb_bit_precision(), nb_bit_padding()).unwrap()
+}
+
+fn nb_bit_padding() -> usize {
+    PARAMS.nb_bit_padding.max(2)
+}
+
+fn nb_bit_precision() -> usize {
+    max_nb_bit_precision(nb_bit_padding())
+}
+
+/// The error tolerated after `nb_operations` operations, each rounding to the precision of the
+/// encoder.
+pub fn tolerance(nb_operations: usize) -> f64 {
+    let precision = 2. * BOUND / (1u64 << nb_bit_precision()) as f64;
+    2. * precision * nb_operations as f64
+}
+
+/// The error of the addition of two cipherfloats of `encoder`: the padded sum has twice its step,
+/// and the bootstrap rounds it to the closest one.
+pub fn add_error(encoder: &crypto_api::Encoder) -> f64 {
+    granularity(encoder)
+}
+
+/// The error of the product of two cipherfloats of `encoder` holding values of at most
+/// VALUE_BOUND. The rounding of the operands of the squares is scaled by their slope, and their
+/// difference is rounded to the step of the interval of the squares.
+pub fn mul_error(encoder: &crypto_api::Encoder) -> f64 {
+    let (min, max) = interval(encoder);
+    // The padded sum of the operands spans twice their interval
+    let max_square = (2. * min.abs().max(max.abs())).powi(2) / 4.;
+    let square_encoder = encoder_like(encoder, 0., max_square);
+    granularity(&square_encoder) + 2. * VALUE_BOUND * granularity(encoder)
+}
+
+/// The error of the product of a cipherfloat of `encoder` by a constant of at most VALUE_BOUND.
+pub fn mul_constant_error(encoder: &crypto_api::Encoder) -> f64 {
+    VALUE_BOUND * granularity(encoder)
+}
+
+/// The error of the dot product of two ciphervectors of dimension `dim`, the errors of the
+/// products and of the additions adding up, followed by the rounding of the decryption.
+pub fn dot_product_error(encoder: &crypto_api::Encoder, dim: usize) -> f64 {
+    dim as f64 * mul_error(encoder) + (dim - 1) as f64 * add_error(encoder) + granularity(encoder)
+}
+
+/// The error of the dot product of a ciphervector and a plainvector of dimension `dim`.
+pub fn constant_dot_product_error(encoder: &crypto_api::Encoder, dim: usize) -> f64 {
+    dim as f64 * mul_constant_error(encoder)
+        + (dim - 1) as f64 * add_error(encoder)
+        + granularity(encoder)
+}
+
+pub fn random_value(rng: &mut StdRng) -> f64 {
+    rng.gen_range(-VALUE_BOUND..VALUE_BOUND)
+}
+
+pub fn random_vector(rng: &mut StdRng, dim: usize) -> Vec<f64> {
+    (0..dim).map(|_| random_value(rng)).collect()
+}
+
+pub fn random_matrix(rng: &mut StdRng, dim_n: usize, dim_m: usize) -> Vec<Vec<f64>> {
+    (0..dim_n).map(|_| random_vector(rng, dim_m)).collect()
+}
+
+/// Checks that the decrypted value is within `tolerance` of the expected one.
+pub fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+    assert!(
+        (actual - expected).abs() <= tolerance,
+        "decrypted {}, expected {} within {}",
+        actual,
+        expected,
+        tolerance
+    );
+}
+
+pub fn assert_vector_close(actual: &Vec<f64>, expected: &Vec<f64>, tolerance: f64) {
+    assert_eq!(actual.len(), expected.len());
+    for i in 0..actual.len() {
+        assert_close(actual[i], expected[i], tolerance);
+    }
+}
+
+pub fn assert_matrix_close(actual: &Vec<Vec<f64>>, expected: &Vec<Vec<f64>>, tolerance: f64) {
+    assert_eq!(actual.len(), expected.len());
+    for i in 0..actual.len() {
+        assert_vector_close(&actual[i], &expected[i], tolerance);
+    }
+}
+
+/// Checks an encrypted operation against its plaintext equivalent on NB_CASES random pairs of
+/// values of [min, max]. The first `nb_encrypted` values of a pair are encrypted with `encoder` and
+/// given to `cipher_op` along with the pair, and the decrypted result has to be within `tolerance`
+/// of `plain_op` on the pair, `tolerance` being given the pair and the encrypted result.
+pub fn check_operation<F, G, T>(
+    encoder: &crypto_api::Encoder,
+    (min, max): (f64, f64),
+    nb_encrypted: usize,
+    cipher_op: F,
+    plain_op: G,
+    tolerance: T,
+) where
+    F: Fn(&[Cipherfloat], [f64; 2]) -> Cipherfloat,
+    G: Fn([f64; 2]) -> f64,
+    T: Fn([f64; 2], &Cipherfloat) -> f64,
+{
+    let sk = zero_key();
+    let mut rng = rng();
+    let mut random_input =
+        || min + (random_value(&mut rng) + VALUE_BOUND) / (2. * VALUE_BOUND) * (max - min);
+    for _ in 0..NB_CASES {
+        let values = [random_input(), random_input()];
+        let encrypted: Vec<Cipherfloat> = values[..nb_encrypted]
+            .iter()
+            .map(|value| sk.encrypt_float_with_encoder(*value, encoder))
+            .collect();
+        let result = cipher_op(&encrypted, values);
+        assert_close(
+            sk.decrypt_float(&result),
+            plain_op(values),
+            tolerance(values, &result),
+        );
+    }
+}
+
+/// Checks an operator on random pairs of cipherfloats against its plaintext equivalent.
+pub fn check_binary<F, G>(nb_operations: usize, cipher_op: F, plain_op: G)
+where
+    F: Fn(&Cipherfloat, &Cipherfloat) -> Cipherfloat,
+    G: Fn(f64, f64) -> f64,
+{
+    check_operation(
+        &encoder(),
+        (-VALUE_BOUND, VALUE_BOUND),
+        2,
+        |e, _| cipher_op(&e[0], &e[1]),
+        |[a, b]| plain_op(a, b),
+        |_, _| tolerance(nb_operations),
+    );
+}
+
+/// Checks an operator between a cipherfloat and a constant against its plaintext equivalent.
+pub fn check_constant<F, G>(nb_operations: usize, cipher_op: F, plain_op: G)
+where
+    F: Fn(&Cipherfloat, f64) -> Cipherfloat,
+    G: Fn(f64, f64) -> f64,
+{
+    check_operation(
+        &encoder(),
+        (-VALUE_BOUND, VALUE_BOUND),
+        1,
+        |e, [_, b]| cipher_op(&e[0], b),
+        |[a, b]| plain_op(a, b),
+        |_, _| tolerance(nb_operations),
+    );
+}
@@ -2,19 +2,24 @@
 use crate::PARAMS;
 
 
-// This macro allows to compute the duration of the execution of the expressions enclosed. Note that
-// the variables are not captured.
+// This macro allows to compute the duration of the execution of the expressions enclosed, and records
+// it with the operations performed as a phase of the profiling report. Note that the variables are
+// not captured.
 #[macro_export]
 macro_rules! measure_duration{
     ($title: tt, [$($block:tt)+]) => {
         println!("{}", $title);
         let __now = std::time::SystemTime::now();
+        let __counters = crate::zqz::profile::counters();
         $(
            $block
         )+
         let __time = __now.elapsed().unwrap().as_millis() as f64 / 1000.;
+        let __counters = crate::zqz::profile::counters() - __counters;
+        crate::zqz::profile::record_phase($title, __time, __counters);
         let __s_time = format!("{} s", __time);
         println!("Duration: {}", __s_time);
+        println!("Bootstraps: {}", __counters.bootstraps);
     }
 }
 /// Compute the round and then the modulo
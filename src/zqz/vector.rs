@@ -1,8 +1,11 @@
 //! A module containing a ciphertext structure.
+use crate::clear;
 use crate::zqz;
-use std::ops::{Add, AddAssign, Mul, Sub, SubAssign};
+use clear::vector::PlainVector;
+use concrete::crypto_api;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
 use std::rc::Rc;
-use zqz::keys::HomomorphicKey;
+use zqz::keys::{EncryptKey, HomomorphicKey};
 
 /// An encrypted message.
 #[derive(Debug, Clone, PartialEq)]
@@ -16,6 +19,31 @@ impl CipherVector {
     pub fn get(&self, i: usize) -> zqz::cipherfloat::Cipherfloat {
         return self.ciphervector[i].clone();
     }
+
+    /// Serializes the ciphertexts of the vector, without the evaluation key.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let ciphertexts: Vec<&crypto_api::LWE> =
+            self.ciphervector.iter().map(|c| &c.cipherfloat).collect();
+        bincode::serialize(&ciphertexts).unwrap()
+    }
+
+    /// Deserializes the ciphertexts serialized by `to_bytes`, they are evaluated with the keys of
+    /// `sk` which have to be the ones they were encrypted with.
+    pub fn from_bytes(bytes: &[u8], sk: &EncryptKey) -> Option<CipherVector> {
+        let ciphertexts: Vec<crypto_api::LWE> = bincode::deserialize(bytes).ok()?;
+        let ciphervector: Vec<zqz::cipherfloat::Cipherfloat> = ciphertexts
+            .into_iter()
+            .map(|cipherfloat| zqz::cipherfloat::Cipherfloat {
+                cipherfloat,
+                evaluation_key: sk.evaluation.clone(),
+            })
+            .collect();
+        Some(CipherVector {
+            dim: ciphervector.len(),
+            ciphervector,
+            evaluation_key: sk.evaluation.clone(),
+        })
+    }
 }
 
 // Adds two ciphervector using the `+` operator.
@@ -105,6 +133,7 @@ impl Mul<&CipherVector> for &CipherVector {
     type Output = zqz::cipherfloat::Cipherfloat;
 
     fn mul(self, other: &CipherVector) -> Self::Output {
+        assert_eq!(self.dim, other.dim, "the vectors of a dot product have the same dimension");
         // addition
         let mut result: zqz::cipherfloat::Cipherfloat =
             &other.ciphervector[0] * &self.ciphervector[0];
@@ -154,6 +183,19 @@ impl Mul<f64> for &CipherVector {
         }
     }
 }
+// Negates all values of the ciphervector using the `-` operator, without bootstrap.
+impl Neg for &CipherVector {
+    type Output = CipherVector;
+
+    fn neg(self) -> Self::Output {
+        CipherVector {
+            ciphervector: self.ciphervector.iter().map(|c| -c).collect(),
+            dim: self.dim,
+            evaluation_key: self.evaluation_key.clone(),
+        }
+    }
+}
+
 // Substract two ciphervectors using the `-` operator.
 impl Sub<&CipherVector> for &CipherVector {
     type Output = CipherVector;
@@ -235,3 +277,177 @@ impl SubAssign<&zqz::cipherfloat::Cipherfloat> for CipherVector {
         self.ciphervector = res.ciphervector;
     }
 }
+
+// Adds a plainvector to a ciphervector value by value using the `+` operator, without bootstrap.
+impl Add<&PlainVector> for &CipherVector {
+    type Output = CipherVector;
+
+    fn add(self, other: &PlainVector) -> Self::Output {
+        let mut result: Vec<zqz::cipherfloat::Cipherfloat> = Vec::new();
+        for i in 0..self.dim {
+            let sum = &self.ciphervector[i] + other.plainvector[i];
+            result.push(sum);
+        }
+
+        CipherVector {
+            ciphervector: result,
+            dim: self.dim,
+            evaluation_key: self.evaluation_key.clone(),
+        }
+    }
+}
+
+// Substracts a plainvector from a ciphervector value by value using the `-` operator, without
+// bootstrap.
+impl Sub<&PlainVector> for &CipherVector {
+    type Output = CipherVector;
+
+    fn sub(self, other: &PlainVector) -> Self::Output {
+        let mut result: Vec<zqz::cipherfloat::Cipherfloat> = Vec::new();
+        for i in 0..self.dim {
+            let sub = &self.ciphervector[i] - other.plainvector[i];
+            result.push(sub);
+        }
+
+        CipherVector {
+            ciphervector: result,
+            dim: self.dim,
+            evaluation_key: self.evaluation_key.clone(),
+        }
+    }
+}
+
+// Multiply this vector by the transpose of a plainvector to return a cipherfloat using `*`
+// operator. The products are multiplications by constants, and the zeros of the plainvector are
+// skipped.
+impl Mul<&PlainVector> for &CipherVector {
+    type Output = zqz::cipherfloat::Cipherfloat;
+
+    fn mul(self, other: &PlainVector) -> Self::Output {
+        assert_eq!(self.dim, other.dim, "the vectors of a dot product have the same dimension");
+        let mut result: Option<zqz::cipherfloat::Cipherfloat> = None;
+        for i in 0..self.dim {
+            if other.plainvector[i] == 0. {
+                continue;
+            }
+            let product = &self.ciphervector[i] * other.plainvector[i];
+            result = Some(match result {
+                Some(sum) => &sum + &product,
+                None => product,
+            });
+        }
+        // A plainvector of zeros still needs an encryption of 0
+        result.unwrap_or_else(|| &self.ciphervector[0] * 0.)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clear::vector::PlainVector;
+    use crate::zqz::testing::*;
+
+    const DIM: usize = 3;
+    // The dot products of the tests, at most DIM, fit in [-4, 4]
+    const DOT_BOUND: f64 = 4.;
+
+    #[test]
+    fn vector_operators_match_plain_vectors() {
+        let sk = zero_key();
+        let encoder = encoder();
+        let mut rng = rng();
+        for _ in 0..NB_CASES {
+            let (a, b) = (random_vector(&mut rng, DIM), random_vector(&mut rng, DIM));
+            let c = random_value(&mut rng);
+            let (p_a, p_b) = (PlainVector::new(a.clone()), PlainVector::new(b.clone()));
+            let e_a = sk.encrypt_vector_with_encoder(&a, &encoder);
+            let e_b = sk.encrypt_vector_with_encoder(&b, &encoder);
+            let e_c = sk.encrypt_float_with_encoder(c, &encoder);
+
+            let sum = sk.decrypt_vector(&(&e_a + &e_b));
+            assert_vector_close(&sum, &(&p_a + &p_b).plainvector, tolerance(1));
+            let sum = sk.decrypt_vector(&(&e_a + c));
+            assert_vector_close(&sum, &(&p_a + c).plainvector, tolerance(1));
+            let sum = sk.decrypt_vector(&(&e_a + &e_c));
+            assert_vector_close(&sum, &(&p_a + c).plainvector, tolerance(1));
+            let difference = sk.decrypt_vector(&(&e_a - &e_b));
+            assert_vector_close(&difference, &(&p_a - &p_b).plainvector, tolerance(1));
+            let difference = sk.decrypt_vector(&(&e_a - c));
+            assert_vector_close(&difference, &(&p_a - c).plainvector, tolerance(1));
+            let opposite = sk.decrypt_vector(&(-&e_a));
+            assert_vector_close(&opposite, &(&p_a * -1.).plainvector, tolerance(1));
+            let product = sk.decrypt_vector(&(&e_a * c));
+            assert_vector_close(&product, &(&p_a * c).plainvector, tolerance(1));
+            let product = sk.decrypt_vector(&(&e_a * &e_c));
+            assert_vector_close(&product, &(&p_a * c).plainvector, tolerance(3));
+        }
+    }
+
+    #[test]
+    fn dot_products_match_plain_vectors() {
+        let sk = zero_key();
+        let encoder = encoder_of(-DOT_BOUND, DOT_BOUND);
+        let mut rng = rng();
+        for _ in 0..NB_CASES {
+            let (a, b) = (random_vector(&mut rng, DIM), random_vector(&mut rng, DIM));
+            let (p_a, p_b) = (PlainVector::new(a.clone()), PlainVector::new(b.clone()));
+            let e_a = sk.encrypt_vector_with_encoder(&a, &encoder);
+            let e_b = sk.encrypt_vector_with_encoder(&b, &encoder);
+
+            // The dot product adds up the errors of its products
+            let dot = sk.decrypt_float(&(&e_a * &e_b));
+            assert_close(dot, &p_a * &p_b, dot_product_error(&encoder, DIM));
+            let dot = sk.decrypt_float(&(&e_a * &p_b));
+            assert_close(dot, &p_a * &p_b, constant_dot_product_error(&encoder, DIM));
+        }
+    }
+
+    #[test]
+    fn mixed_operators_match_plain_vectors() {
+        let sk = zero_key();
+        let encoder = encoder();
+        let mut rng = rng();
+        for _ in 0..NB_CASES {
+            let (a, b) = (random_vector(&mut rng, DIM), random_vector(&mut rng, DIM));
+            let (p_a, p_b) = (PlainVector::new(a.clone()), PlainVector::new(b.clone()));
+            let e_a = sk.encrypt_vector_with_encoder(&a, &encoder);
+
+            let sum = sk.decrypt_vector(&(&e_a + &p_b));
+            assert_vector_close(&sum, &(&p_a + &p_b).plainvector, tolerance(1));
+            let difference = sk.decrypt_vector(&(&e_a - &p_b));
+            assert_vector_close(&difference, &(&p_a - &p_b).plainvector, tolerance(1));
+        }
+        // The zeros are skipped, down to an encryption of 0
+        let e_a = sk.encrypt_vector_with_encoder(&vec![0.5, -1., 0.25], &encoder);
+        let dot = sk.decrypt_float(&(&e_a * &PlainVector::new(vec![0., 0.5, 0.])));
+        assert_close(dot, -0.5, constant_dot_product_error(&encoder, 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "the same dimension")]
+    fn dot_product_of_mismatched_vectors() {
+        let sk = zero_key();
+        let e_a = sk.encrypt_vector_with_encoder(&vec![0.5, -1., 0.25], &encoder());
+        let _ = &e_a * &PlainVector::new(vec![0.5, -1.]);
+        let dot = sk.decrypt_float(&(&e_a * &PlainVector::new(vec![0.; 3])));
+        assert_close(dot, 0., constant_dot_product_error(&encoder, 1));
+    }
+
+    #[test]
+    fn serialize_vectors() {
+        let sk = zero_key();
+        let v = vec![0.5, -0.25, 1.];
+        let e_v = sk.encrypt_vector_with_encoder(&v, &encoder());
+        let restored = super::CipherVector::from_bytes(&e_v.to_bytes(), &sk).unwrap();
+        assert_eq!(restored, e_v);
+    }
+
+    #[test]
+    #[ignore]
+    fn dot_product_with_real_keys() {
+        let sk = crate::zqz::keys::EncryptKey::new();
+        let encoder = encoder();
+        let e_a = sk.encrypt_vector_with_encoder(&vec![0.5, -1., 0.25], &encoder);
+        let e_b = sk.encrypt_vector_with_encoder(&vec![1., 0.5, -0.5], &encoder);
+        assert_close(sk.decrypt_float(&(&e_a * &e_b)), -0.125, tolerance(4 * DIM));
+    }
+}